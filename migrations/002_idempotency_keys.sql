@@ -0,0 +1,18 @@
+-- Idempotency-Key untuk /trigger dan /notify: hasil request pertama disimpan dan
+-- dikembalikan lagi untuk request berikutnya dengan key yang sama sampai kedaluwarsa.
+-- owner: pemanggil (`app:<keys.id>` atau `public` tanpa auth); key hanya unik per pemanggil.
+-- request_hash: SHA-256 body request, supaya key yang dipakai ulang dengan body lain ditolak.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    scope VARCHAR(64) NOT NULL,
+    owner VARCHAR(64) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    request_hash VARCHAR(64) NOT NULL,
+    -- NULL = request pertama masih diproses
+    status_code INTEGER,
+    response TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (scope, owner, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires_at ON idempotency_keys (expires_at);
@@ -0,0 +1,10 @@
+-- Payload lengkap untuk mode fetch-on-push: push hanya membawa id, service worker
+-- mengambil isinya lewat GET /payloads/:id.
+CREATE TABLE IF NOT EXISTS push_payloads (
+    id VARCHAR(64) PRIMARY KEY,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_payloads_expires_at ON push_payloads (expires_at);
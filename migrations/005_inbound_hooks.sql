@@ -0,0 +1,16 @@
+-- Webhook masuk (GitHub, Grafana, JSON generic) per app, diteruskan ke channel.
+-- mapping: JSON {title, body, url} berisi JSON pointer ke payload masuk.
+-- token: auth hook tanpa secret (X-Hook-Token / ?token=); hook dengan secret memakai signature.
+CREATE TABLE IF NOT EXISTS inbound_hooks (
+    id SERIAL PRIMARY KEY,
+    key_id INTEGER NOT NULL REFERENCES keys (id) ON DELETE CASCADE,
+    adapter VARCHAR(32) NOT NULL,
+    token VARCHAR(32) NOT NULL,
+    channel VARCHAR(164) NOT NULL,
+    event VARCHAR(255) NOT NULL,
+    secret TEXT,
+    mapping TEXT NOT NULL DEFAULT '{}',
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (key_id, adapter)
+);
@@ -0,0 +1,13 @@
+-- Riwayat notifikasi (/notify dan API Gotify). key_id NULL = tanpa app (/notify).
+CREATE TABLE IF NOT EXISTS notification_history (
+    id BIGSERIAL PRIMARY KEY,
+    key_id INTEGER REFERENCES keys (id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    message TEXT NOT NULL,
+    priority INTEGER NOT NULL DEFAULT 0,
+    -- JSON extras gaya Gotify
+    extras TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_history_key_id ON notification_history (key_id, id DESC);
@@ -0,0 +1,6 @@
+-- Service account Firebase (JSON) per app untuk transport FCM HTTP v1.
+CREATE TABLE IF NOT EXISTS fcm_credentials (
+    key_id INTEGER PRIMARY KEY REFERENCES keys (id) ON DELETE CASCADE,
+    service_account TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- Kredensial APNs token-based (.p8) per app untuk transport iOS.
+CREATE TABLE IF NOT EXISTS apns_credentials (
+    key_id INTEGER PRIMARY KEY REFERENCES keys (id) ON DELETE CASCADE,
+    team_id VARCHAR(32) NOT NULL,
+    auth_key_id VARCHAR(32) NOT NULL,
+    private_key TEXT NOT NULL,
+    -- bundle ID aplikasi (header apns-topic)
+    topic VARCHAR(255) NOT NULL,
+    sandbox BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,24 @@
+-- Alamat email per user (user_id dari aplikasi, sama seperti saat /subscribe) untuk fallback
+-- email notifikasi yang ditargetkan ke user. user_id hanya unik di dalam satu app (keys.id).
+CREATE TABLE IF NOT EXISTS recipients (
+    key_id INTEGER NOT NULL REFERENCES keys (id) ON DELETE CASCADE,
+    user_id VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (key_id, user_id)
+);
+
+-- Hasil pengiriman per penerima (push / email) untuk notifikasi di notification_history.
+CREATE TABLE IF NOT EXISTS notification_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    history_id BIGINT NOT NULL REFERENCES notification_history (id) ON DELETE CASCADE,
+    user_id VARCHAR(255) NOT NULL,
+    -- push | email
+    channel VARCHAR(16) NOT NULL,
+    -- sent | failed | skipped
+    status VARCHAR(16) NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_deliveries_history_id ON notification_deliveries (history_id);
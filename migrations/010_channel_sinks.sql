@@ -0,0 +1,15 @@
+-- Sink per channel: URL incoming webhook (Slack / Discord / Teams) yang menerima pesan
+-- terformat setiap kali channel di-trigger. channel boleh berupa pola wildcard.
+-- key_id NULL = hanya trigger tanpa app (/trigger); selain itu hanya trigger app tersebut.
+CREATE TABLE IF NOT EXISTS channel_sinks (
+    id SERIAL PRIMARY KEY,
+    key_id INTEGER REFERENCES keys (id) ON DELETE CASCADE,
+    channel VARCHAR(164) NOT NULL,
+    url TEXT NOT NULL,
+    -- json | slack | discord | teams
+    format VARCHAR(16) NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_channel_sinks_channel ON channel_sinks (channel);
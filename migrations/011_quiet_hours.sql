@@ -0,0 +1,24 @@
+-- Quiet hours per app (keys.id): jam lokal start_time..end_time di zona waktu timezone.
+-- end_time < start_time = melewati tengah malam.
+CREATE TABLE IF NOT EXISTS quiet_hours (
+    key_id INTEGER PRIMARY KEY REFERENCES keys (id) ON DELETE CASCADE,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Push yang ditunda karena quiet hours; dikirim scheduler saat deliver_at tercapai.
+CREATE TABLE IF NOT EXISTS deferred_pushes (
+    id BIGSERIAL PRIMARY KEY,
+    subscription_id VARCHAR(64) NOT NULL,
+    -- Payload JSON belum ditandatangani; ditandatangani dengan key app saat dikirim
+    payload TEXT NOT NULL,
+    urgency VARCHAR(16),
+    -- Channel trigger, supaya frequency cap per channel tetap berlaku saat dikirim
+    channels TEXT[] NOT NULL DEFAULT '{}',
+    deliver_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_deferred_pushes_deliver_at ON deferred_pushes (deliver_at);
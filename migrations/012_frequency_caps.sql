@@ -0,0 +1,22 @@
+-- Batas frekuensi push per subscription: maksimal max_pushes dalam window_secs terakhir.
+-- key_id NULL = semua app; channel NULL = semua channel (boleh pola wildcard).
+CREATE TABLE IF NOT EXISTS frequency_caps (
+    id SERIAL PRIMARY KEY,
+    key_id INTEGER REFERENCES keys (id) ON DELETE CASCADE,
+    channel VARCHAR(164),
+    max_pushes INTEGER NOT NULL,
+    window_secs INTEGER NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Satu baris per push terkirim yang terkena cap (sliding window). id dipakai untuk melepas
+-- slot push yang gagal terkirim.
+CREATE TABLE IF NOT EXISTS frequency_counts (
+    id BIGSERIAL PRIMARY KEY,
+    cap_id INTEGER NOT NULL REFERENCES frequency_caps (id) ON DELETE CASCADE,
+    subscription_id VARCHAR(64) NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_frequency_counts_lookup ON frequency_counts (cap_id, subscription_id, sent_at);
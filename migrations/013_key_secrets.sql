@@ -0,0 +1,4 @@
+-- Secret HMAC per app untuk REST API gaya Pusher, auth channel private/presence, dan token
+-- user; terpisah dari private key ECDSA di kolom key. Baris lama diberi secret acak.
+ALTER TABLE keys ADD COLUMN IF NOT EXISTS secret VARCHAR(64) NOT NULL
+    DEFAULT replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
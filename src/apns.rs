@@ -0,0 +1,362 @@
+//! Transport Apple Push Notification service (HTTP/2, token-based auth) untuk aplikasi iOS.
+//!
+//! - Subscription `type: "apns"`: `endpoint` = device token (hex), `app_id` = ID app (tabel keys).
+//! - Kredensial per app di tabel `apns_credentials` (`PUT /api/keys/:id/apns`): Team ID, Key ID,
+//!   isi file `.p8`, topic (bundle ID), dan `sandbox`. Provider token (JWT ES256) di-cache per
+//!   app dan diperbarui tiap `TOKEN_REFRESH` (Apple menolak token berumur > 1 jam).
+//! - `title`/`body` (dari `/notify` atau `data` trigger) menjadi `aps.alert`, `badge`/`sound`
+//!   menjadi `aps.badge`/`aps.sound`; envelope bertanda tangan ikut sebagai custom key.
+//! - `APNS_ENDPOINT` mengganti host Apple (mis. stand-in lokal untuk pengujian, HTTP/1.1 atau
+//!   HTTP/2).
+
+use async_trait::async_trait;
+use isahc::{
+    config::{Configurable, VersionNegotiation},
+    AsyncReadResponseExt, HttpClient, Request,
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::time::{Duration, Instant};
+use web_push::Urgency;
+
+use crate::state::StoredSubscription;
+use crate::transport::{ErrorClass, Notification, TokenCache, Transport, TransportError};
+
+const PRODUCTION_ENDPOINT: &str = "https://api.push.apple.com";
+const SANDBOX_ENDPOINT: &str = "https://api.sandbox.push.apple.com";
+const DEFAULT_SOUND: &str = "default";
+const TOKEN_REFRESH: Duration = Duration::from_secs(50 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kredensial APNs satu app.
+#[derive(Clone, Deserialize, FromRow)]
+pub struct ApnsCredentials {
+    pub team_id: String,
+    /// Key ID dari file `.p8` (`AuthKey_<key_id>.p8`).
+    pub key_id: String,
+    /// Isi file `.p8` (PEM PKCS#8).
+    pub private_key: String,
+    /// Bundle ID aplikasi (header `apns-topic`).
+    pub topic: String,
+    #[serde(default)]
+    pub sandbox: bool,
+}
+
+pub fn validate_credentials(c: &ApnsCredentials) -> Result<(), String> {
+    if c.team_id.trim().is_empty() || c.key_id.trim().is_empty() || c.topic.trim().is_empty() {
+        return Err("team_id, key_id dan topic wajib diisi".to_string());
+    }
+    EncodingKey::from_ec_pem(c.private_key.as_bytes())
+        .map_err(|_| "private_key bukan file .p8 (EC PEM) yang valid".to_string())?;
+    Ok(())
+}
+
+/// Device token: hex, minimal 64 karakter (32 byte); Apple bisa memperpanjangnya, maksimal
+/// 100 byte. Token masuk ke path URL request, jadi karakter lain ditolak.
+pub fn validate_device_token(token: &str) -> Result<(), String> {
+    if (64..=200).contains(&token.len()) && token.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err("token APNs harus hex, 64-200 karakter".to_string())
+    }
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    iat: i64,
+}
+
+struct ProviderToken {
+    credentials: ApnsCredentials,
+    token: String,
+    issued_at: Instant,
+}
+
+pub struct Apns {
+    db: PgPool,
+    client: HttpClient,
+    /// Override host Apple (`APNS_ENDPOINT`).
+    endpoint: Option<String>,
+    /// Provider token per app (key_id tabel keys).
+    tokens: TokenCache<ProviderToken>,
+}
+
+impl Apns {
+    pub fn new(db: PgPool) -> anyhow::Result<Self> {
+        let endpoint = std::env::var("APNS_ENDPOINT")
+            .ok()
+            .filter(|e| !e.trim().is_empty());
+        Self::with_endpoint(db, endpoint)
+    }
+
+    /// `endpoint` None = host Apple (HTTP/2 wajib). Stand-in boleh HTTP/1.1.
+    pub fn with_endpoint(db: PgPool, endpoint: Option<String>) -> anyhow::Result<Self> {
+        let version = if endpoint.is_some() {
+            VersionNegotiation::latest_compatible()
+        } else {
+            VersionNegotiation::http2()
+        };
+        let client = HttpClient::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .version_negotiation(version)
+            .build()?;
+        let endpoint = endpoint.map(|e| e.trim_end_matches('/').to_string());
+        Ok(Self {
+            db,
+            client,
+            endpoint,
+            tokens: TokenCache::default(),
+        })
+    }
+
+    /// Buang provider token yang di-cache (setelah kredensial app diganti / dihapus).
+    pub async fn forget(&self, app_id: i32) {
+        self.tokens.forget(app_id);
+    }
+
+    /// (kredensial, provider token) untuk app; token dibuat ulang jika sudah tua.
+    async fn provider_token(
+        &self,
+        app_id: i32,
+    ) -> Result<(ApnsCredentials, String), TransportError> {
+        let slot = self.tokens.slot(app_id);
+        let mut cached = slot.lock().await;
+        if let Some(t) = cached.as_ref() {
+            if t.issued_at.elapsed() < TOKEN_REFRESH {
+                return Ok((t.credentials.clone(), t.token.clone()));
+            }
+        }
+        let credentials: Option<ApnsCredentials> = sqlx::query_as(
+            "SELECT team_id, auth_key_id AS key_id, private_key, topic, sandbox FROM apns_credentials WHERE key_id = $1",
+        )
+        .bind(app_id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| TransportError::retryable(format!("load apns credentials: {}", e)))?;
+        let Some(credentials) = credentials else {
+            return Err(TransportError::permanent(format!(
+                "app {} belum punya kredensial APNs",
+                app_id
+            )));
+        };
+        let key = EncodingKey::from_ec_pem(credentials.private_key.as_bytes())
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(credentials.key_id.clone());
+        let claims = Claims {
+            iss: &credentials.team_id,
+            iat: chrono::Utc::now().timestamp(),
+        };
+        let token = jsonwebtoken::encode(&header, &claims, &key)
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        *cached = Some(ProviderToken {
+            credentials: credentials.clone(),
+            token: token.clone(),
+            issued_at: Instant::now(),
+        });
+        Ok((credentials, token))
+    }
+
+    fn host<'a>(&'a self, credentials: &ApnsCredentials) -> &'a str {
+        match &self.endpoint {
+            Some(e) => e,
+            None if credentials.sandbox => SANDBOX_ENDPOINT,
+            None => PRODUCTION_ENDPOINT,
+        }
+    }
+}
+
+/// Body APNs: `aps` dari title/body/badge/sound payload + envelope bertanda tangan.
+fn notification(payload: &[u8]) -> serde_json::Value {
+    let n = Notification::parse(payload);
+    let mut aps = serde_json::json!({
+        "alert": {
+            "title": n.title(),
+            "body": n.text("body")
+        },
+        "sound": n.field("sound").unwrap_or_else(|| DEFAULT_SOUND.into())
+    });
+    if let Some(badge) = n.field("badge").filter(|b| b.is_u64()) {
+        aps["badge"] = badge;
+    }
+    if n.inner.get("payload_id").is_some() {
+        // Fetch-on-push: aplikasi mengambil payload lengkap sendiri.
+        aps["mutable-content"] = 1.into();
+    }
+    serde_json::json!({
+        "aps": aps,
+        "payload": n.envelope["payload"],
+        "signature": n.envelope["signature"]
+    })
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(default)]
+    reason: String,
+}
+
+#[async_trait]
+impl Transport for Apns {
+    async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        urgency: Option<Urgency>,
+    ) -> Result<(), TransportError> {
+        let Some(app_id) = subscription.app_id else {
+            return Err(TransportError::permanent("subscription apns tanpa app_id"));
+        };
+        let (credentials, token) = self.provider_token(app_id).await?;
+        // 10 = kirim segera, 5 = boleh ditunda demi hemat baterai.
+        let priority = match urgency {
+            Some(Urgency::VeryLow | Urgency::Low) => "5",
+            _ => "10",
+        };
+        let url = format!(
+            "{}/3/device/{}",
+            self.host(&credentials),
+            subscription.endpoint
+        );
+        let request = Request::post(url)
+            .header("authorization", format!("bearer {}", token))
+            .header("apns-topic", &credentials.topic)
+            .header("apns-push-type", "alert")
+            .header("apns-priority", priority)
+            .header("content-type", "application/json")
+            .body(notification(payload).to_string())
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let mut response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| TransportError::retryable(format!("apns request: {}", e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        let reason = serde_json::from_str::<ErrorResponse>(&text)
+            .map(|e| e.reason)
+            .unwrap_or_default();
+        let message = format!("apns {}: {}", status, reason);
+        if reason == "ExpiredProviderToken" || reason == "InvalidProviderToken" {
+            // Provider token dibuat ulang di percobaan berikutnya.
+            self.forget(app_id).await;
+            return Err(TransportError::retryable(message));
+        }
+        Err(TransportError::new(
+            self.classify(status.as_u16(), &reason),
+            message,
+        ))
+    }
+
+    /// `body` = `reason` dari respons APNs. 410 = token tidak aktif lagi (aplikasi dihapus),
+    /// `BadDeviceToken` = token salah lingkungan / format.
+    fn classify(&self, status: u16, reason: &str) -> ErrorClass {
+        match (status, reason) {
+            (410, _) | (_, "BadDeviceToken" | "Unregistered") => ErrorClass::Expired,
+            (429 | 500..=599, _) => ErrorClass::Retryable,
+            _ => ErrorClass::Permanent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock;
+
+    const APP_ID: i32 = 1;
+    const TOKEN: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn subscription(token: &str) -> StoredSubscription {
+        serde_json::from_value(serde_json::json!({
+            "type": "apns",
+            "endpoint": token,
+            "app_id": APP_ID
+        }))
+        .expect("subscription")
+    }
+
+    async fn apns_with_cached_token(endpoint: String) -> Apns {
+        // Tidak pernah terhubung: provider token sudah di-cache.
+        let db = PgPool::connect_lazy("postgres://localhost/unused").expect("lazy pool");
+        let apns = Apns::with_endpoint(db, Some(endpoint)).expect("apns");
+        *apns.tokens.slot(APP_ID).lock().await = Some(ProviderToken {
+            credentials: ApnsCredentials {
+                team_id: "TEAM123456".to_string(),
+                key_id: "KEY1234567".to_string(),
+                private_key: String::new(),
+                topic: "com.example.app".to_string(),
+                sandbox: false,
+            },
+            token: "provider-token".to_string(),
+            issued_at: Instant::now(),
+        });
+        apns
+    }
+
+    #[test]
+    fn device_token_must_be_hex() {
+        assert!(validate_device_token(TOKEN).is_ok());
+        assert!(validate_device_token(&TOKEN[..62]).is_err());
+        assert!(validate_device_token(&format!("{}/../x", TOKEN)).is_err());
+    }
+
+    #[tokio::test]
+    async fn send_posts_alert_to_device_path() {
+        let (endpoint, requests) = mock::serve(|_| (200, String::new())).await;
+        let apns = apns_with_cached_token(endpoint).await;
+        let payload = br#"{"payload":"{\"title\":\"Halo\",\"body\":\"Isi\",\"badge\":3}","signature":"sig"}"#;
+        apns.send(&subscription(TOKEN), payload, Some(Urgency::Low))
+            .await
+            .expect("send");
+
+        let requests = requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.path, format!("/3/device/{}", TOKEN));
+        assert_eq!(request.header("authorization"), "bearer provider-token");
+        assert_eq!(request.header("apns-topic"), "com.example.app");
+        assert_eq!(request.header("apns-push-type"), "alert");
+        assert_eq!(request.header("apns-priority"), "5");
+        let body = request.json();
+        assert_eq!(body["aps"]["alert"]["title"], "Halo");
+        assert_eq!(body["aps"]["alert"]["body"], "Isi");
+        assert_eq!(body["aps"]["badge"], 3);
+        assert_eq!(body["signature"], "sig");
+    }
+
+    #[tokio::test]
+    async fn send_classifies_error_responses() {
+        // Respons mock ditentukan oleh device token di path.
+        let (endpoint, _) = mock::serve(|r| {
+            let token = r.path.trim_start_matches("/3/device/").to_string();
+            let (status, reason) = match token.as_str() {
+                "gone" => (410, "Unregistered"),
+                "bad" => (400, "BadDeviceToken"),
+                "throttled" => (429, "TooManyRequests"),
+                "unavailable" => (503, "ServiceUnavailable"),
+                _ => (400, "BadTopic"),
+            };
+            (status, format!(r#"{{"reason":"{}"}}"#, reason))
+        })
+        .await;
+        let apns = apns_with_cached_token(endpoint).await;
+        for (token, class) in [
+            ("gone", ErrorClass::Expired),
+            ("bad", ErrorClass::Expired),
+            ("throttled", ErrorClass::Retryable),
+            ("unavailable", ErrorClass::Retryable),
+            ("topic", ErrorClass::Permanent),
+        ] {
+            let err = apns
+                .send(&subscription(token), b"{}", None)
+                .await
+                .expect_err(token);
+            assert_eq!(err.class, class, "{}", token);
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Nama channel hierarkis + wildcard.
+//!
+//! Grammar:
+//! - Nama channel terdiri dari segmen yang dipisah `.` atau `/` (keduanya setara,
+//!   `alerts/cpu` sama dengan `alerts.cpu`).
+//! - Segmen berisi huruf/angka ASCII dan `_ - = @ , ;`, tidak boleh kosong.
+//! - Panjang nama maksimal 164 karakter.
+//! - Saat subscribe, segmen boleh berupa wildcard:
+//!   - `*` cocok dengan tepat satu segmen (`orders.store-12.*` cocok `orders.store-12.created`),
+//!   - `#` cocok dengan nol atau lebih segmen dan hanya boleh di segmen terakhir
+//!     (`orders.#` cocok `orders`, `orders.store-12`, `orders.store-12.created`).
+//! - Channel tujuan saat trigger harus konkret (tanpa wildcard).
+//! - Channel `private-*` / `presence-*` tidak pernah cocok lewat wildcard.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::presence;
+
+pub const MAX_CHANNEL_LEN: usize = 164;
+
+const WILDCARD_ONE: &str = "*";
+const WILDCARD_MANY: &str = "#";
+
+fn segments(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['.', '/'])
+}
+
+fn valid_segment_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '=' | '@' | ',' | ';')
+}
+
+/// Validasi pola channel untuk subscribe (wildcard diizinkan).
+pub fn validate_pattern(name: &str) -> Result<(), String> {
+    validate(name, true)
+}
+
+/// Validasi nama channel konkret untuk trigger (tanpa wildcard).
+pub fn validate_name(name: &str) -> Result<(), String> {
+    validate(name, false)
+}
+
+fn validate(name: &str, allow_wildcard: bool) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("nama channel tidak boleh kosong".to_string());
+    }
+    if name.len() > MAX_CHANNEL_LEN {
+        return Err(format!(
+            "nama channel '{}' melebihi {} karakter",
+            name, MAX_CHANNEL_LEN
+        ));
+    }
+    let segs: Vec<&str> = segments(name).collect();
+    for (i, seg) in segs.iter().enumerate() {
+        if *seg == WILDCARD_ONE || *seg == WILDCARD_MANY {
+            if !allow_wildcard {
+                return Err(format!("channel '{}' tidak boleh memakai wildcard", name));
+            }
+            if *seg == WILDCARD_MANY && i != segs.len() - 1 {
+                return Err(format!(
+                    "wildcard '#' hanya boleh di segmen terakhir ('{}')",
+                    name
+                ));
+            }
+            continue;
+        }
+        if seg.is_empty() {
+            return Err(format!("channel '{}' memiliki segmen kosong", name));
+        }
+        if !seg.chars().all(valid_segment_char) {
+            return Err(format!(
+                "channel '{}' mengandung karakter tidak valid",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Apakah pola (boleh wildcard) cocok dengan nama channel konkret.
+/// Channel private/presence hanya cocok dengan nama persis, tidak lewat wildcard.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if presence::requires_auth(name) {
+        return pattern == name;
+    }
+    let mut segs = segments(name);
+    for p in segments(pattern) {
+        if p == WILDCARD_MANY {
+            return true;
+        }
+        match segs.next() {
+            Some(s) if p == WILDCARD_ONE || p == s => {}
+            _ => return false,
+        }
+    }
+    segs.next().is_none()
+}
+
+/// Filter penerima trigger (lihat `TriggerBody`): OR `any`, AND `all`, kecuali `exclude`.
+#[derive(Clone, Debug, Default)]
+pub struct ChannelFilter {
+    pub any: Vec<String>,
+    pub all: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl ChannelFilter {
+    /// Apakah penerima dengan daftar pola channel ini lolos filter.
+    pub fn accepts(&self, patterns: &[String]) -> bool {
+        let subscribed = |name: &String| patterns.iter().any(|p| matches(p, name));
+        (self.any.is_empty() || self.any.iter().any(subscribed))
+            && self.all.iter().all(subscribed)
+            && !self.exclude.iter().any(subscribed)
+    }
+}
+
+/// Trie pola channel -> index subscription, supaya pencocokan trigger tidak
+/// perlu memindai seluruh subscription.
+#[derive(Clone, Default, Debug)]
+pub struct ChannelIndex {
+    root: Node,
+}
+
+#[derive(Clone, Default, Debug)]
+struct Node {
+    children: HashMap<String, Node>,
+    any_one: Option<Box<Node>>,
+    /// Subscription dengan pola yang berakhir tepat di node ini.
+    exact: Vec<usize>,
+    /// Subscription dengan pola `...#` di node ini.
+    any_many: Vec<usize>,
+}
+
+impl ChannelIndex {
+    pub fn insert(&mut self, pattern: &str, sub: usize) {
+        let mut node = &mut self.root;
+        for seg in segments(pattern) {
+            if seg == WILDCARD_MANY {
+                node.any_many.push(sub);
+                return;
+            }
+            node = if seg == WILDCARD_ONE {
+                node.any_one.get_or_insert_with(Default::default)
+            } else {
+                node.children.entry(seg.to_string()).or_default()
+            };
+        }
+        node.exact.push(sub);
+    }
+
+    /// Index subscription yang polanya cocok dengan channel konkret `name`.
+    pub fn lookup(&self, name: &str, out: &mut BTreeSet<usize>) {
+        let segs: Vec<&str> = segments(name).collect();
+        let wildcards = !presence::requires_auth(name);
+        Self::collect(&self.root, &segs, wildcards, out);
+    }
+
+    fn collect(node: &Node, segs: &[&str], wildcards: bool, out: &mut BTreeSet<usize>) {
+        if wildcards {
+            out.extend(node.any_many.iter().copied());
+        }
+        let Some((first, rest)) = segs.split_first() else {
+            out.extend(node.exact.iter().copied());
+            return;
+        };
+        if let Some(child) = node.children.get(*first) {
+            Self::collect(child, rest, wildcards, out);
+        }
+        if let Some(child) = node.any_one.as_deref().filter(|_| wildcards) {
+            Self::collect(child, rest, wildcards, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(index: &ChannelIndex, name: &str) -> Vec<usize> {
+        let mut out = BTreeSet::new();
+        index.lookup(name, &mut out);
+        out.into_iter().collect()
+    }
+
+    #[test]
+    fn names_accept_segments_and_both_separators() {
+        for name in [
+            "default",
+            "orders.store-12.created",
+            "alerts/cpu",
+            "a_b=c@d,e;f",
+        ] {
+            assert_eq!(validate_name(name), Ok(()), "{}", name);
+        }
+        assert_eq!(validate_name(&"a".repeat(MAX_CHANNEL_LEN)), Ok(()));
+    }
+
+    #[test]
+    fn names_reject_empty_long_and_invalid() {
+        for name in [
+            "",
+            "orders..created",
+            ".orders",
+            "orders.",
+            "orders/",
+            "ord ers",
+            "örders",
+        ] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        assert!(validate_name(&"a".repeat(MAX_CHANNEL_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn wildcards_only_in_patterns() {
+        assert!(validate_name("orders.*").is_err());
+        assert!(validate_name("orders.#").is_err());
+        assert_eq!(validate_pattern("orders.*.created"), Ok(()));
+        assert_eq!(validate_pattern("orders.#"), Ok(()));
+        assert_eq!(validate_pattern("#"), Ok(()));
+        // `#` hanya di segmen terakhir; wildcard harus satu segmen utuh.
+        assert!(validate_pattern("#.created").is_err());
+        assert!(validate_pattern("orders.#.created").is_err());
+        assert!(validate_pattern("orders.store*").is_err());
+    }
+
+    #[test]
+    fn star_matches_exactly_one_segment() {
+        assert!(matches("orders.*", "orders.eu"));
+        assert!(matches("orders/*", "orders.eu"));
+        assert!(matches("*.created", "orders.created"));
+        assert!(!matches("orders.*", "orders"));
+        // `*` tidak melewati `.`.
+        assert!(!matches("orders.*", "orders.eu.created"));
+        assert!(!matches("*", "orders.eu"));
+    }
+
+    #[test]
+    fn hash_matches_zero_or_more_segments() {
+        assert!(matches("orders.#", "orders"));
+        assert!(matches("orders.#", "orders.eu"));
+        assert!(matches("orders.#", "orders.eu.created"));
+        assert!(matches("#", "anything.at.all"));
+        assert!(!matches("orders.#", "ordersx"));
+        assert!(!matches("orders.#", "invoices.eu"));
+    }
+
+    #[test]
+    fn exact_patterns_and_separators() {
+        assert!(matches("alerts.cpu", "alerts/cpu"));
+        assert!(!matches("alerts.cpu", "alerts.cpu.high"));
+        assert!(!matches("alerts.cpu.high", "alerts.cpu"));
+    }
+
+    #[test]
+    fn private_channels_never_match_wildcards() {
+        assert!(!matches("#", "private-orders"));
+        assert!(!matches("*", "presence-room"));
+        assert!(matches("presence-room", "presence-room"));
+    }
+
+    #[test]
+    fn filter_combines_any_all_and_exclude() {
+        let patterns = vec!["orders.#".to_string(), "vip".to_string()];
+        let filter = |any: &[&str], all: &[&str], exclude: &[&str]| ChannelFilter {
+            any: any.iter().map(|s| s.to_string()).collect(),
+            all: all.iter().map(|s| s.to_string()).collect(),
+            exclude: exclude.iter().map(|s| s.to_string()).collect(),
+        };
+        assert!(filter(&[], &[], &[]).accepts(&patterns));
+        assert!(filter(&["orders.eu", "news"], &[], &[]).accepts(&patterns));
+        assert!(!filter(&["news"], &[], &[]).accepts(&patterns));
+        assert!(filter(&[], &["orders.eu", "vip"], &[]).accepts(&patterns));
+        assert!(!filter(&[], &["orders.eu", "beta"], &[]).accepts(&patterns));
+        assert!(!filter(&["orders.eu"], &[], &["vip"]).accepts(&patterns));
+    }
+
+    #[test]
+    fn index_lookup_follows_matches() {
+        let mut index = ChannelIndex::default();
+        index.insert("orders.eu.created", 0);
+        index.insert("orders.*.created", 1);
+        index.insert("orders.#", 2);
+        index.insert("#", 3);
+        index.insert("alerts/cpu", 4);
+        index.insert("presence-room", 5);
+
+        assert_eq!(lookup(&index, "orders.eu.created"), vec![0, 1, 2, 3]);
+        assert_eq!(lookup(&index, "orders.us.created"), vec![1, 2, 3]);
+        assert_eq!(lookup(&index, "orders"), vec![2, 3]);
+        assert_eq!(lookup(&index, "orders.eu"), vec![2, 3]);
+        assert_eq!(lookup(&index, "alerts.cpu"), vec![3, 4]);
+        assert_eq!(lookup(&index, "presence-room"), vec![5]);
+        assert_eq!(lookup(&index, "private-other"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn index_lookup_agrees_with_matches() {
+        let patterns = ["a.b", "a.*", "a.#", "*.b", "#", "a.*.c", "b"];
+        let names = ["a", "b", "a.b", "a.c", "c.b", "a.b.c", "a.x.c", "a.b.c.d"];
+        let mut index = ChannelIndex::default();
+        for (i, p) in patterns.iter().enumerate() {
+            index.insert(p, i);
+        }
+        for name in names {
+            let expected: Vec<usize> = patterns
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| matches(p, name))
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(lookup(&index, name), expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Transport Firebase Cloud Messaging (HTTP v1) untuk aplikasi Android.
+//!
+//! - Subscription `type: "fcm"`: `endpoint` = registration token, `app_id` = ID app (tabel keys).
+//! - Service account JSON per app di tabel `fcm_credentials` (`PUT /api/keys/:id/fcm`). Access
+//!   token OAuth2 (JWT RS256 ke `token_uri`) di-cache per app sampai hampir kedaluwarsa.
+//! - Payload bertanda tangan dikirim sebagai data message (`payload` + `signature`, sama seperti
+//!   envelope Web Push); urgency `high` menjadi `android.priority = HIGH`.
+//! - `FCM_ENDPOINT` (default `https://fcm.googleapis.com`) dan `token_uri` di service account
+//!   bisa diarahkan ke mock lokal untuk pengujian.
+
+use async_trait::async_trait;
+use isahc::{config::Configurable, AsyncReadResponseExt, HttpClient, Request};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use web_push::Urgency;
+
+use crate::state::StoredSubscription;
+use crate::transport::{ErrorClass, TokenCache, Transport, TransportError};
+
+const DEFAULT_ENDPOINT: &str = "https://fcm.googleapis.com";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+const ASSERTION_LIFETIME_SECS: i64 = 3600;
+/// Access token diperbarui sedikit sebelum kedaluwarsa.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_token_uri() -> String {
+    DEFAULT_TOKEN_URI.to_string()
+}
+
+/// Field service account Google yang dipakai.
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccount {
+    pub project_id: String,
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default = "default_token_uri")]
+    pub token_uri: String,
+}
+
+/// Validasi JSON service account (field wajib dan private key RSA).
+pub fn parse_service_account(json: &serde_json::Value) -> Result<ServiceAccount, String> {
+    let account: ServiceAccount = serde_json::from_value(json.clone())
+        .map_err(|e| format!("Service account tidak valid: {}", e))?;
+    if account.project_id.trim().is_empty() || account.client_email.trim().is_empty() {
+        return Err("project_id dan client_email wajib diisi".to_string());
+    }
+    EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+        .map_err(|_| "private_key bukan RSA PEM yang valid".to_string())?;
+    Ok(account)
+}
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+struct AccessToken {
+    project_id: String,
+    token: String,
+    expires_at: Instant,
+}
+
+pub struct Fcm {
+    db: PgPool,
+    client: HttpClient,
+    endpoint: String,
+    /// Access token per app (key_id).
+    tokens: TokenCache<AccessToken>,
+}
+
+impl Fcm {
+    pub fn new(db: PgPool) -> anyhow::Result<Self> {
+        let endpoint = std::env::var("FCM_ENDPOINT")
+            .ok()
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_ENDPOINT.to_string());
+        Self::with_endpoint(db, &endpoint)
+    }
+
+    pub fn with_endpoint(db: PgPool, endpoint: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            db,
+            client: HttpClient::builder().timeout(REQUEST_TIMEOUT).build()?,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            tokens: TokenCache::default(),
+        })
+    }
+
+    /// Buang access token yang di-cache (setelah service account app diganti / dihapus).
+    pub async fn forget(&self, app_id: i32) {
+        self.tokens.forget(app_id);
+    }
+
+    /// (project_id, access token) untuk app; minta token baru jika belum ada / kedaluwarsa.
+    async fn access_token(&self, app_id: i32) -> Result<(String, String), TransportError> {
+        let slot = self.tokens.slot(app_id);
+        let mut cached = slot.lock().await;
+        if let Some(t) = cached.as_ref() {
+            if t.expires_at > Instant::now() + REFRESH_MARGIN {
+                return Ok((t.project_id.clone(), t.token.clone()));
+            }
+        }
+        let row: Option<(String,)> =
+            sqlx::query_as("SELECT service_account FROM fcm_credentials WHERE key_id = $1")
+                .bind(app_id)
+                .fetch_optional(&self.db)
+                .await
+                .map_err(|e| TransportError::retryable(format!("load fcm credentials: {}", e)))?;
+        let Some((json,)) = row else {
+            return Err(TransportError::permanent(format!(
+                "app {} belum punya service account FCM",
+                app_id
+            )));
+        };
+        let account = serde_json::from_str(&json)
+            .map_err(|e| e.to_string())
+            .and_then(|v| parse_service_account(&v))
+            .map_err(TransportError::permanent)?;
+        let token = self.fetch_token(&account).await?;
+        *cached = Some(AccessToken {
+            project_id: account.project_id.clone(),
+            token: token.access_token.clone(),
+            expires_at: Instant::now()
+                + Duration::from_secs(token.expires_in.unwrap_or(ASSERTION_LIFETIME_SECS as u64)),
+        });
+        Ok((account.project_id, token.access_token))
+    }
+
+    /// OAuth2 JWT bearer grant (RFC 7523) dengan service account.
+    async fn fetch_token(&self, account: &ServiceAccount) -> Result<TokenResponse, TransportError> {
+        let now = chrono::Utc::now().timestamp();
+        let claims = Claims {
+            iss: &account.client_email,
+            scope: SCOPE,
+            aud: &account.token_uri,
+            iat: now,
+            exp: now + ASSERTION_LIFETIME_SECS,
+        };
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let form = serde_urlencoded::to_string([
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let request = Request::post(&account.token_uri)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(form)
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let mut response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| TransportError::retryable(format!("fcm token request: {}", e)))?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            let class = if status.is_server_error() {
+                ErrorClass::Retryable
+            } else {
+                ErrorClass::Permanent
+            };
+            return Err(TransportError::new(
+                class,
+                format!("fcm token request: {} {}", status, text),
+            ));
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| TransportError::permanent(format!("fcm token response: {}", e)))
+    }
+}
+
+/// Envelope `{"payload","signature"}` menjadi data message FCM (nilai harus string).
+fn data_message(payload: &[u8]) -> serde_json::Value {
+    match serde_json::from_slice::<serde_json::Value>(payload) {
+        Ok(serde_json::Value::Object(map)) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = match v {
+                        serde_json::Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    (k, v.into())
+                })
+                .collect(),
+        ),
+        _ => serde_json::json!({ "payload": String::from_utf8_lossy(payload) }),
+    }
+}
+
+#[async_trait]
+impl Transport for Fcm {
+    async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        urgency: Option<Urgency>,
+    ) -> Result<(), TransportError> {
+        let Some(app_id) = subscription.app_id else {
+            return Err(TransportError::permanent("subscription fcm tanpa app_id"));
+        };
+        let (project_id, token) = self.access_token(app_id).await?;
+        let priority = if matches!(urgency, Some(Urgency::High)) {
+            "HIGH"
+        } else {
+            "NORMAL"
+        };
+        let body = serde_json::json!({
+            "message": {
+                "token": subscription.endpoint,
+                "data": data_message(payload),
+                "android": { "priority": priority }
+            }
+        });
+        let url = format!("{}/v1/projects/{}/messages:send", self.endpoint, project_id);
+        let request = Request::post(url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(body.to_string())
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let mut response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| TransportError::retryable(format!("fcm request: {}", e)))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let text = response.text().await.unwrap_or_default();
+        let message = format!("fcm {}: {}", status, text);
+        if status.as_u16() == 401 {
+            // Token dicabut / kedaluwarsa lebih awal: minta ulang lalu coba lagi.
+            self.forget(app_id).await;
+            return Err(TransportError::retryable(message));
+        }
+        Err(TransportError::new(
+            self.classify(status.as_u16(), &text),
+            message,
+        ))
+    }
+
+    /// Token registrasi yang sudah tidak berlaku: 404 / `UNREGISTERED`.
+    fn classify(&self, status: u16, body: &str) -> ErrorClass {
+        match status {
+            404 => ErrorClass::Expired,
+            _ if body.contains("UNREGISTERED") => ErrorClass::Expired,
+            408 | 429 | 500..=599 => ErrorClass::Retryable,
+            _ => ErrorClass::Permanent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::mock;
+    use std::collections::HashMap;
+
+    const APP_ID: i32 = 1;
+
+    fn lazy_db() -> PgPool {
+        // Tidak pernah terhubung: token sudah di-cache, jadi kredensial tidak dibaca dari DB.
+        PgPool::connect_lazy("postgres://localhost/unused").expect("lazy pool")
+    }
+
+    fn subscription(token: &str) -> StoredSubscription {
+        serde_json::from_value(serde_json::json!({
+            "type": "fcm",
+            "endpoint": token,
+            "app_id": APP_ID
+        }))
+        .expect("subscription")
+    }
+
+    async fn fcm_with_cached_token(endpoint: &str) -> Fcm {
+        let fcm = Fcm::with_endpoint(lazy_db(), endpoint).expect("fcm");
+        *fcm.tokens.slot(APP_ID).lock().await = Some(AccessToken {
+            project_id: "demo-project".to_string(),
+            token: "cached-token".to_string(),
+            expires_at: Instant::now() + Duration::from_secs(3600),
+        });
+        fcm
+    }
+
+    #[tokio::test]
+    async fn send_posts_data_message_to_project() {
+        let (endpoint, requests) = mock::serve(|_| (200, "{}".to_string())).await;
+        let fcm = fcm_with_cached_token(&endpoint).await;
+        let payload = br#"{"payload":"{\"title\":\"Halo\"}","signature":"sig"}"#;
+        fcm.send(&subscription("device-token"), payload, Some(Urgency::High))
+            .await
+            .expect("send");
+
+        let requests = requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.path, "/v1/projects/demo-project/messages:send");
+        assert_eq!(request.header("authorization"), "Bearer cached-token");
+        let message = &request.json()["message"];
+        assert_eq!(message["token"], "device-token");
+        assert_eq!(message["data"]["payload"], r#"{"title":"Halo"}"#);
+        assert_eq!(message["data"]["signature"], "sig");
+        assert_eq!(message["android"]["priority"], "HIGH");
+    }
+
+    #[tokio::test]
+    async fn send_classifies_error_responses() {
+        // Status mock ditentukan oleh token perangkat.
+        let (endpoint, _) = mock::serve(|r| {
+            let token = r.json()["message"]["token"].as_str().unwrap_or("").to_string();
+            match token.as_str() {
+                "not-found" => (404, "{}".to_string()),
+                "unregistered" => (
+                    400,
+                    r#"{"error":{"details":[{"errorCode":"UNREGISTERED"}]}}"#.to_string(),
+                ),
+                "throttled" => (429, "{}".to_string()),
+                "unavailable" => (503, "{}".to_string()),
+                _ => (400, r#"{"error":{"status":"INVALID_ARGUMENT"}}"#.to_string()),
+            }
+        })
+        .await;
+        let fcm = fcm_with_cached_token(&endpoint).await;
+        for (token, class) in [
+            ("not-found", ErrorClass::Expired),
+            ("unregistered", ErrorClass::Expired),
+            ("throttled", ErrorClass::Retryable),
+            ("unavailable", ErrorClass::Retryable),
+            ("invalid", ErrorClass::Permanent),
+        ] {
+            let err = fcm
+                .send(&subscription(token), b"{}", None)
+                .await
+                .expect_err(token);
+            assert_eq!(err.class, class, "{}", token);
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_token_uses_jwt_bearer_grant() {
+        let (token_uri, requests) = mock::serve(|_| {
+            (
+                200,
+                r#"{"access_token":"fresh-token","expires_in":3599}"#.to_string(),
+            )
+        })
+        .await;
+        let key = openssl::rsa::Rsa::generate(2048).expect("rsa");
+        let account = ServiceAccount {
+            project_id: "demo-project".to_string(),
+            client_email: "push@demo-project.iam.gserviceaccount.com".to_string(),
+            private_key: String::from_utf8(key.private_key_to_pem().expect("pem")).expect("pem utf8"),
+            token_uri: format!("{}/token", token_uri),
+        };
+        let fcm = Fcm::with_endpoint(lazy_db(), DEFAULT_ENDPOINT).expect("fcm");
+        let token = fcm.fetch_token(&account).await.expect("token");
+        assert_eq!(token.access_token, "fresh-token");
+        assert_eq!(token.expires_in, Some(3599));
+
+        let requests = requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.path, "/token");
+        let form: HashMap<String, String> =
+            serde_urlencoded::from_str(&request.body).expect("form");
+        assert_eq!(form["grant_type"], "urn:ietf:params:oauth:grant-type:jwt-bearer");
+        let header = jsonwebtoken::decode_header(&form["assertion"]).expect("jwt header");
+        assert_eq!(header.alg, Algorithm::RS256);
+    }
+}
@@ -0,0 +1,217 @@
+//! Frequency capping: batas jumlah push per subscription dalam sliding window, mis. "maksimal
+//! 5 push marketing per device per hari".
+//!
+//! - Dikonfigurasi lewat `/api/frequency-caps`: `key_id` (app, kosong = semua app), `channel`
+//!   (pola, kosong = semua channel), `max_pushes`, `window_secs` (default 1 hari).
+//! - Cap berlaku untuk subscription dengan `app_id` = `key_id` dan push yang salah satu
+//!   channel tujuannya cocok dengan `channel`.
+//! - Di `push_service::send_to_all`, sebelum `PushService::send` slot dipesan secara atomik di
+//!   `frequency_counts` per cap yang berlaku (`reserve`); subscription yang sudah mencapai
+//!   batas dilewati dan dihitung sebagai `capped`. Slot push yang gagal terkirim dilepas lagi.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::channel;
+use crate::state::{AppState, StoredSubscription};
+
+pub const DEFAULT_WINDOW_SECS: i32 = 24 * 60 * 60;
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FrequencyCapRow {
+    pub id: i32,
+    pub key_id: Option<i32>,
+    pub channel: Option<String>,
+    pub max_pushes: i32,
+    pub window_secs: i32,
+    pub active: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn default_window_secs() -> i32 {
+    DEFAULT_WINDOW_SECS
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFrequencyCapBody {
+    #[serde(default)]
+    pub key_id: Option<i32>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    pub max_pushes: i32,
+    #[serde(default = "default_window_secs")]
+    pub window_secs: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFrequencyCapBody {
+    pub max_pushes: Option<i32>,
+    pub window_secs: Option<i32>,
+    pub active: Option<bool>,
+}
+
+pub fn validate_limits(max_pushes: i32, window_secs: i32) -> Result<(), String> {
+    if max_pushes < 1 {
+        return Err("max_pushes minimal 1".to_string());
+    }
+    if window_secs < 1 {
+        return Err("window_secs minimal 1".to_string());
+    }
+    Ok(())
+}
+
+impl FrequencyCapRow {
+    fn applies(&self, sub: &StoredSubscription, channels: &[String]) -> bool {
+        self.key_id.is_none_or(|k| sub.app_id == Some(k))
+            && self
+                .channel
+                .as_deref()
+                .is_none_or(|pattern| channels.iter().any(|c| channel::matches(pattern, c)))
+    }
+}
+
+/// Cap yang berlaku per subscription (urutan sama dengan `subscriptions`).
+pub struct Evaluation {
+    caps: Vec<Vec<FrequencyCapRow>>,
+}
+
+impl Evaluation {
+    fn none(len: usize) -> Self {
+        Self {
+            caps: vec![Vec::new(); len],
+        }
+    }
+}
+
+/// Slot cap yang sudah dipesan untuk satu push (baris `frequency_counts`).
+#[derive(Debug, Default)]
+pub struct Reservation {
+    ids: Vec<i64>,
+}
+
+/// Cap yang berlaku untuk push ke `channels`. Gagal membaca database = tidak ada yang di-cap.
+pub async fn evaluate(
+    state: &AppState,
+    subscriptions: &[StoredSubscription],
+    channels: &[String],
+) -> Evaluation {
+    match try_evaluate(&state.db, subscriptions, channels).await {
+        Ok(evaluation) => evaluation,
+        Err(e) => {
+            warn!(error = %e, "frequency cap lookup failed, sending uncapped");
+            Evaluation::none(subscriptions.len())
+        }
+    }
+}
+
+async fn try_evaluate(
+    db: &PgPool,
+    subscriptions: &[StoredSubscription],
+    channels: &[String],
+) -> sqlx::Result<Evaluation> {
+    // Urut id supaya lock di `reserve` selalu diambil dengan urutan yang sama.
+    let caps: Vec<FrequencyCapRow> = sqlx::query_as(
+        "SELECT id, key_id, channel, max_pushes, window_secs, active, created_at FROM frequency_caps WHERE active ORDER BY id",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(Evaluation {
+        caps: subscriptions
+            .iter()
+            .map(|s| caps.iter().filter(|c| c.applies(s, channels)).cloned().collect())
+            .collect(),
+    })
+}
+
+/// Pesan slot di semua cap yang berlaku untuk subscription ke-`index`, sekaligus (check dan
+/// catat dalam satu transaksi, dikunci per cap + subscription). Return None jika salah satu
+/// cap sudah penuh; gagal database = dikirim tanpa cap.
+pub async fn reserve(
+    db: &PgPool,
+    evaluation: &Evaluation,
+    index: usize,
+    subscription: &StoredSubscription,
+) -> Option<Reservation> {
+    let caps = &evaluation.caps[index];
+    if caps.is_empty() {
+        return Some(Reservation::default());
+    }
+    match try_reserve(db, caps, &subscription.id).await {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            warn!(error = %e, "frequency cap reservation failed, sending uncapped");
+            Some(Reservation::default())
+        }
+    }
+}
+
+async fn try_reserve(
+    db: &PgPool,
+    caps: &[FrequencyCapRow],
+    subscription_id: &str,
+) -> sqlx::Result<Option<Reservation>> {
+    let mut tx = db.begin().await?;
+    let mut ids = Vec::with_capacity(caps.len());
+    for cap in caps {
+        sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+            .bind(cap.id)
+            .bind(subscription_id)
+            .execute(&mut *tx)
+            .await?;
+        let row: Option<(i64,)> = sqlx::query_as(
+            "INSERT INTO frequency_counts (cap_id, subscription_id) SELECT $1, $2 WHERE (SELECT COUNT(*) FROM frequency_counts WHERE cap_id = $1 AND subscription_id = $2 AND sent_at > NOW() - $3 * INTERVAL '1 second') < $4 RETURNING id",
+        )
+        .bind(cap.id)
+        .bind(subscription_id)
+        .bind(cap.window_secs)
+        .bind(i64::from(cap.max_pushes))
+        .fetch_optional(&mut *tx)
+        .await?;
+        match row {
+            Some((id,)) => ids.push(id),
+            // Drop `tx` = rollback slot cap lain yang sudah dipesan.
+            None => return Ok(None),
+        }
+    }
+    tx.commit().await?;
+    Ok(Some(Reservation { ids }))
+}
+
+/// Lepas slot yang dipesan untuk push yang gagal terkirim.
+pub async fn release(db: &PgPool, reservation: Reservation) {
+    if reservation.ids.is_empty() {
+        return;
+    }
+    if let Err(e) = sqlx::query("DELETE FROM frequency_counts WHERE id = ANY($1)")
+        .bind(reservation.ids)
+        .execute(db)
+        .await
+    {
+        warn!(error = %e, "release frequency cap reservation failed");
+    }
+}
+
+/// Hapus catatan yang sudah keluar dari window cap-nya, setiap `CLEANUP_INTERVAL`.
+pub fn spawn_cleanup(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match sqlx::query(
+                "DELETE FROM frequency_counts c USING frequency_caps f WHERE f.id = c.cap_id AND c.sent_at < NOW() - f.window_secs * INTERVAL '1 second'",
+            )
+            .execute(&state.db)
+            .await
+            {
+                Ok(r) if r.rows_affected() > 0 => {
+                    info!(removed = r.rows_affected(), "frequency counts cleaned up")
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "frequency counts cleanup failed"),
+            }
+        }
+    });
+}
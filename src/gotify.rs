@@ -0,0 +1,268 @@
+//! API message kompatibel Gotify (https://gotify.net/api-docs), untuk tooling homelab
+//! yang sudah mengirim ke Gotify.
+//!
+//! - Token aplikasi Gotify = `secret` app di tabel keys (bukan public key); dikirim lewat
+//!   `?token=`, header `X-Gotify-Key`, atau `Authorization: Bearer`.
+//! - `POST /message` (JSON atau form): broadcast lewat pipeline trigger (event `message`), lalu
+//!   disimpan di riwayat notifikasi jika berhasil. Priority 0-10 dipetakan ke header `Urgency`
+//!   Web Push.
+//! - `GET /message?limit=&since=`: riwayat pesan milik app token tersebut, terbaru dulu.
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use serde::Deserialize;
+use web_push::Urgency;
+
+use crate::handlers::{publish_trigger, TriggerBody};
+use crate::history::{self, HistoryRow};
+use crate::state::AppState;
+
+const GOTIFY_EVENT: &str = "message";
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub since: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct MessageBody {
+    pub message: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub priority: Option<i32>,
+    #[serde(default)]
+    pub extras: Option<serde_json::Value>,
+}
+
+struct App {
+    id: i32,
+    name: String,
+}
+
+fn error(status: StatusCode, description: &str) -> (StatusCode, Json<serde_json::Value>) {
+    // Format error Gotify.
+    (
+        status,
+        Json(serde_json::json!({
+            "error": status.canonical_reason().unwrap_or(""),
+            "errorCode": status.as_u16(),
+            "errorDescription": description
+        })),
+    )
+}
+
+fn token<'a>(query: &'a TokenQuery, headers: &'a HeaderMap) -> Option<&'a str> {
+    query
+        .token
+        .as_deref()
+        .or_else(|| headers.get("X-Gotify-Key").and_then(|v| v.to_str().ok()))
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix("Bearer "))
+        })
+        .filter(|t| !t.is_empty())
+}
+
+async fn authenticate(
+    state: &AppState,
+    query: &TokenQuery,
+    headers: &HeaderMap,
+) -> Result<App, (StatusCode, Json<serde_json::Value>)> {
+    let Some(token) = token(query, headers) else {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "you need to provide a valid access token or user credentials to access this api",
+        ));
+    };
+    let row: Option<(i32, String)> =
+        sqlx::query_as("SELECT id, name FROM keys WHERE secret = $1")
+            .bind(token)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+    match row {
+        Some((id, name)) => Ok(App { id, name }),
+        None => Err(error(
+            StatusCode::UNAUTHORIZED,
+            "you need to provide a valid access token or user credentials to access this api",
+        )),
+    }
+}
+
+fn urgency(priority: i32) -> Urgency {
+    match priority {
+        i32::MIN..=0 => Urgency::VeryLow,
+        1..=3 => Urgency::Low,
+        4..=7 => Urgency::Normal,
+        _ => Urgency::High,
+    }
+}
+
+/// Objek message Gotify.
+fn message_json(row: &HistoryRow) -> serde_json::Value {
+    let mut msg = serde_json::json!({
+        "id": row.id,
+        "appid": row.key_id,
+        "message": row.message,
+        "title": row.title,
+        "priority": row.priority,
+        "date": row.created_at
+    });
+    if let Some(extras) = row
+        .extras
+        .as_deref()
+        .and_then(|e| serde_json::from_str::<serde_json::Value>(e).ok())
+    {
+        msg["extras"] = extras;
+    }
+    msg
+}
+
+fn parse_body(headers: &HeaderMap, body: &[u8]) -> Result<MessageBody, String> {
+    let is_form = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        serde_urlencoded::from_bytes(body).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_slice(body).map_err(|e| e.to_string())
+    }
+}
+
+/// `POST /message`
+pub async fn create_message(
+    State(state): State<AppState>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let app = match authenticate(&state, &query, &headers).await {
+        Ok(app) => app,
+        Err(rejected) => return rejected,
+    };
+    let msg = match parse_body(&headers, &body) {
+        Ok(m) if !m.message.is_empty() => m,
+        Ok(_) => return error(StatusCode::BAD_REQUEST, "Field 'message' is required"),
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e),
+    };
+    // Gotify memakai nama aplikasi jika title kosong.
+    let title = msg
+        .title
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| app.name.clone());
+    let priority = msg.priority.unwrap_or(0);
+    let click = msg
+        .extras
+        .as_ref()
+        .and_then(|e| e.pointer("/client::notification/click/url"))
+        .and_then(|u| u.as_str())
+        .map(str::to_string);
+
+    let id = match history::next_id(&state.db).await {
+        Ok(id) => id,
+        Err(e) => {
+            tracing::error!(%e, "reserve gotify message id");
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "failed to store message");
+        }
+    };
+    let trigger = TriggerBody {
+        channels: Vec::new(),
+        channels_all: Vec::new(),
+        exclude_channels: Vec::new(),
+        exclude: Vec::new(),
+        event: GOTIFY_EVENT.to_string(),
+        data: serde_json::json!({
+            "id": id,
+            "appid": app.id,
+            "title": title,
+            "body": msg.message,
+            "priority": priority,
+            "url": click
+        }),
+        fetch_on_push: None,
+        urgency: Some(urgency(priority)),
+        // Tanpa channel = semua subscription, tapi hanya milik app token ini.
+        app_id: Some(app.id),
+    };
+    if let Err(e) = publish_trigger(&state, &trigger).await {
+        return error(e.status, &e.message);
+    }
+    if let Err(e) = history::record_as(
+        &state.db,
+        id,
+        Some(app.id),
+        &title,
+        &msg.message,
+        priority,
+        msg.extras.as_ref(),
+    )
+    .await
+    {
+        tracing::error!(%e, "record gotify message");
+        return error(StatusCode::INTERNAL_SERVER_ERROR, "failed to store message");
+    }
+    state.record_last_notification(&title, &msg.message).await;
+
+    let row = HistoryRow {
+        id,
+        key_id: Some(app.id),
+        title,
+        message: msg.message,
+        priority,
+        extras: msg.extras.map(|e| e.to_string()),
+        created_at: chrono::Utc::now(),
+    };
+    (StatusCode::OK, Json(message_json(&row)))
+}
+
+/// `GET /message`
+pub async fn list_messages(
+    State(state): State<AppState>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let app = match authenticate(&state, &query, &headers).await {
+        Ok(app) => app,
+        Err(rejected) => return rejected,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let since = query.since.filter(|s| *s > 0);
+    let rows = match history::list(&state.db, Some(app.id), since, limit).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::error!(%e, "list gotify messages");
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "failed to load messages");
+        }
+    };
+    let messages: Vec<serde_json::Value> = rows.iter().map(message_json).collect();
+    let mut paging = serde_json::json!({
+        "size": messages.len(),
+        "since": rows.last().map_or(0, |r| r.id),
+        "limit": limit
+    });
+    if rows.len() as i64 == limit {
+        if let Some(last) = rows.last() {
+            paging["next"] = format!("/message?limit={}&since={}", limit, last.id).into();
+        }
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "messages": messages, "paging": paging })),
+    )
+}
@@ -1,17 +1,43 @@
 use axum::{
-    extract::{Path, State},
-    http::{header::SET_COOKIE, HeaderValue, StatusCode},
-    response::{AppendHeaders, IntoResponse},
+    extract::{
+        ws::{CloseFrame, Message, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        AppendHeaders, IntoResponse,
+    },
     Json,
 };
+use futures_util::stream::{self, Stream, StreamExt};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
 use serde::Deserialize;
 use tracing::{info, warn};
-use web_push::SubscriptionInfo;
+use web_push::Urgency;
 
+use crate::apns::ApnsCredentials;
 use crate::auth::{create_token, AuthUser, AUTH_COOKIE_NAME};
+use crate::channel::{self, ChannelFilter};
+use crate::frequency_caps::{self, CreateFrequencyCapBody, FrequencyCapRow, UpdateFrequencyCapBody};
+use crate::history;
+use crate::idempotency;
+use crate::inbound_hooks::{self, CreateHookBody, HookRow, UpdateHookBody};
 use crate::keys::{CreateKeyBody, KeyRow, UpdateKeyBody};
+use crate::presence::{self, PresenceGuard};
 use crate::push_service;
-use crate::state::{save_subscriptions, AppState, LastNotification, SubscriptionKeys};
+use crate::quiet_hours::{self, QuietHours};
+use crate::realtime::RealtimeEvent;
+use crate::recipients::{self, RecipientRow, UpsertRecipientBody};
+use crate::signature;
+use crate::sinks::{self, CreateSinkBody, SinkRow, UpdateSinkBody};
+use crate::webhook_transport::WebhookFormat;
+use crate::webhooks::{self, CreateWebhookBody, DeliveryRow, UpdateWebhookBody, WebhookRow};
+use crate::websocket;
+use crate::state::{
+    save_subscriptions, AppState, StoredSubscription, SubscriptionKeys, SubscriptionKind,
+};
 
 #[derive(Deserialize)]
 pub struct SubscribeKeys {
@@ -21,9 +47,36 @@ pub struct SubscribeKeys {
 
 #[derive(Deserialize)]
 pub struct SubscribeBody {
+    /// `webpush` (default), `fcm`, atau `apns`. Subscription `webhook` hanya lewat
+    /// `/api/keys/:id/webhook-subscriptions`.
+    #[serde(default, rename = "type")]
+    pub kind: SubscriptionKind,
+    /// Endpoint Web Push, atau token perangkat untuk `fcm` / `apns`.
+    #[serde(alias = "token")]
     pub endpoint: String,
-    pub keys: SubscribeKeys,
-    /// Channel names (gaya Pusher). Kosong = channel "default".
+    /// Wajib untuk `webpush`.
+    #[serde(default)]
+    pub keys: Option<SubscribeKeys>,
+    /// App (tabel keys) yang kredensial transport-nya dipakai; wajib untuk `fcm` / `apns`.
+    /// Untuk `webpush` opsional, dipakai untuk quiet hours app.
+    #[serde(default)]
+    pub app_id: Option<i32>,
+    /// Public key app (`PUSH_NOTIF_APP_KEY` di SDK), alternatif `app_id`.
+    #[serde(default)]
+    pub app: Option<String>,
+    /// ID user di aplikasi (opsional), untuk `/notify` dengan `users`. Butuh app dan
+    /// `user_auth`.
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// HMAC-SHA256 hex atas `user_id` dengan secret app, dibuat backend aplikasi.
+    #[serde(default)]
+    pub user_auth: Option<String>,
+    /// Quiet hours subscriber `{start, end, timezone}` (lihat `crate::quiet_hours`);
+    /// `start` == `end` menghapus jendela yang tersimpan.
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    /// Channel names (gaya Pusher), boleh wildcard `*` / `#` (lihat `crate::channel`).
+    /// Kosong = channel "default".
     #[serde(default)]
     pub channels: Vec<String>,
 }
@@ -34,26 +87,359 @@ pub async fn vapid_public_key(State(state): State<AppState>) -> impl IntoRespons
     }))
 }
 
+#[derive(Deserialize)]
+pub struct SigningKeyQuery {
+    #[serde(default)]
+    pub app_id: Option<i32>,
+}
+
+/// Public key verifikasi payload: milik app (`?app_id=`, sama dengan public key app) atau key
+/// server untuk subscription tanpa app.
+pub async fn signing_public_key(
+    State(state): State<AppState>,
+    Query(query): Query<SigningKeyQuery>,
+) -> impl IntoResponse {
+    let Some(app_id) = query.app_id else {
+        return (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "publicKey": state.push_service.signing_public_key_base64url()
+            })),
+        );
+    };
+    let row: Option<(String,)> = sqlx::query_as("SELECT public_key FROM keys WHERE id = $1")
+        .bind(app_id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten();
+    match row {
+        Some((public_key,)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "publicKey": public_key })),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+    }
+}
+
+/// Channel subscription push: pola publik saja. Channel private/presence butuh auth per
+/// koneksi, jadi hanya lewat `/stream/subscribe` atau WebSocket.
+fn validate_push_channels(channels: &[String]) -> Result<(), String> {
+    channels.iter().try_for_each(|c| {
+        if presence::requires_auth(c) {
+            return Err(format!(
+                "channel {} butuh auth; subscribe lewat /stream atau WebSocket",
+                c
+            ));
+        }
+        channel::validate_pattern(c)
+    })
+}
+
 pub async fn subscribe(
     State(state): State<AppState>,
-    Json(body): Json<SubscribeBody>,
+    Json(mut body): Json<SubscribeBody>,
 ) -> impl IntoResponse {
-    let keys = SubscriptionKeys {
-        p256dh: body.keys.p256dh,
-        auth: body.keys.auth,
+    if let (None, Some(public_key)) = (body.app_id, body.app.as_deref()) {
+        let app: Option<(i32,)> = sqlx::query_as("SELECT id FROM keys WHERE public_key = $1")
+            .bind(public_key)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+        let Some((app_id,)) = app else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+            );
+        };
+        body.app_id = Some(app_id);
+    }
+    if let Err(message) = validate_push_channels(&body.channels) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    if body.endpoint.trim().is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": "endpoint / token wajib diisi" })),
+        );
+    }
+    if let Some(q) = body.quiet_hours.as_ref().filter(|q| q.enabled()) {
+        if let Err(message) = quiet_hours::validate(&state.db, q).await {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+    }
+    if let (SubscriptionKind::WebPush, Some(app_id)) = (body.kind, body.app_id) {
+        let key_exists: Option<(i32,)> = sqlx::query_as("SELECT id FROM keys WHERE id = $1")
+            .bind(app_id)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+        if key_exists.is_none() {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+            );
+        }
+    }
+    let (keys, app_id) = match body.kind {
+        SubscriptionKind::WebPush => match body.keys {
+            Some(k) => (
+                SubscriptionKeys {
+                    p256dh: k.p256dh,
+                    auth: k.auth,
+                },
+                body.app_id,
+            ),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "ok": false, "message": "keys wajib untuk subscription webpush" })),
+                );
+            }
+        },
+        SubscriptionKind::Fcm | SubscriptionKind::Apns => {
+            if body.kind == SubscriptionKind::Apns {
+                if let Err(message) = crate::apns::validate_device_token(&body.endpoint) {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(serde_json::json!({ "ok": false, "message": message })),
+                    );
+                }
+            }
+            let Some(app_id) = body.app_id else {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "ok": false, "message": "app_id wajib untuk subscription fcm / apns" })),
+                );
+            };
+            let (query, missing) = if body.kind == SubscriptionKind::Fcm {
+                (
+                    "SELECT key_id FROM fcm_credentials WHERE key_id = $1",
+                    "Service account FCM untuk app tidak ditemukan",
+                )
+            } else {
+                (
+                    "SELECT key_id FROM apns_credentials WHERE key_id = $1",
+                    "Kredensial APNs untuk app tidak ditemukan",
+                )
+            };
+            let configured: Option<(i32,)> = sqlx::query_as(query)
+                .bind(app_id)
+                .fetch_optional(&state.db)
+                .await
+                .ok()
+                .flatten();
+            if configured.is_none() {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(serde_json::json!({ "ok": false, "message": missing })),
+                );
+            }
+            (SubscriptionKeys::default(), Some(app_id))
+        }
+        SubscriptionKind::Webhook => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": "subscription webhook dibuat lewat /api/keys/:id/webhook-subscriptions" })),
+            );
+        }
     };
+    if let Some(user_id) = body.user_id.as_deref() {
+        if let Err(message) = recipients::validate_user_id(user_id) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        let Some(app_id) = app_id else {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": "user_id butuh app" })),
+            );
+        };
+        let secret: Option<(String,)> = sqlx::query_as("SELECT secret FROM keys WHERE id = $1")
+            .bind(app_id)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+        let user_auth = body.user_auth.as_deref().unwrap_or("");
+        if !secret.is_some_and(|(secret,)| recipients::verify_user_auth(&secret, user_id, user_auth)) {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({ "ok": false, "message": "user_auth tidak valid" })),
+            );
+        }
+    }
     let endpoint = body.endpoint.clone();
-    let count = {
+    let (id, channels, count) = {
         let mut subs = state.subscriptions.write().await;
-        subs.add(endpoint.clone(), keys, body.channels);
+        if subs
+            .subscriptions
+            .iter()
+            .any(|s| s.endpoint == endpoint && s.kind == SubscriptionKind::Webhook)
+        {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "ok": false, "message": "endpoint dipakai subscription webhook" })),
+            );
+        }
+        let Some(id) = subs.add(StoredSubscription {
+            id: String::new(),
+            kind: body.kind,
+            endpoint: endpoint.clone(),
+            keys,
+            app_id,
+            user_id: body.user_id,
+            format: None,
+            quiet_hours: body.quiet_hours,
+            channels: body.channels,
+        }) else {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "ok": false, "message": "endpoint sudah terdaftar dengan keys atau app lain" })),
+            );
+        };
+        let channels = subs
+            .subscriptions
+            .iter()
+            .find(|s| s.id == id)
+            .map(|s| s.channels.clone())
+            .unwrap_or_default();
         let to_save = subs.clone();
         if let Err(e) = save_subscriptions(&to_save).await {
             warn!(error = %e, "failed to persist subscriptions");
         }
-        subs.len()
+        (id, channels, subs.len())
+    };
+    info!(endpoint = %endpoint, kind = ?body.kind, count, "subscription added");
+    state.webhooks.dispatch(
+        webhooks::SUBSCRIPTION_CREATED,
+        app_id,
+        serde_json::json!({ "id": id, "endpoint": endpoint, "channels": channels }),
+    );
+    (StatusCode::CREATED, Json(serde_json::json!({ "ok": true, "id": id })))
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeBody {
+    pub endpoint: String,
+    /// `keys.auth` subscription Web Push (hanya diketahui browser pemiliknya); wajib untuk
+    /// `webpush`. Token `fcm` / `apns` sendiri sudah menjadi bukti kepemilikan.
+    #[serde(default)]
+    pub auth: Option<String>,
+}
+
+/// Pemanggil memegang subscription ini: `keys.auth` cocok untuk Web Push, token perangkat untuk
+/// transport native. Subscription webhook hanya lewat `/api`.
+fn owns_subscription(s: &StoredSubscription, auth: Option<&str>) -> bool {
+    match s.kind {
+        SubscriptionKind::WebPush => auth.is_some_and(|auth| s.auth_matches(auth)),
+        SubscriptionKind::Fcm | SubscriptionKind::Apns => true,
+        SubscriptionKind::Webhook => false,
+    }
+}
+
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    Json(body): Json<UnsubscribeBody>,
+) -> impl IntoResponse {
+    let removed = {
+        let mut subs = state.subscriptions.write().await;
+        let removed = subs.remove_where(|s| {
+            s.endpoint == body.endpoint && owns_subscription(s, body.auth.as_deref())
+        });
+        if removed.is_some() {
+            let to_save = subs.clone();
+            if let Err(e) = save_subscriptions(&to_save).await {
+                warn!(error = %e, "failed to persist subscriptions");
+            }
+        }
+        removed
+    };
+    let Some(s) = removed else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Subscription tidak ditemukan" })),
+        );
+    };
+    info!(endpoint = %s.endpoint, "subscription removed");
+    state.webhooks.dispatch(
+        webhooks::SUBSCRIPTION_DELETED,
+        s.app_id,
+        serde_json::json!({ "id": s.id, "endpoint": s.endpoint, "channels": s.channels }),
+    );
+    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+}
+
+#[derive(Deserialize)]
+pub struct InteractionBody {
+    /// `clicked` atau `closed`.
+    pub action: String,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Endpoint subscription device yang menampilkan notifikasi.
+    pub endpoint: String,
+    /// `keys.auth` subscription Web Push, bukti bahwa laporan datang dari device tersebut.
+    #[serde(default)]
+    pub auth: Option<String>,
+}
+
+/// Dilaporkan `sw.js` saat notifikasi diklik / ditutup; diteruskan ke webhook app subscription.
+pub async fn interaction(
+    State(state): State<AppState>,
+    Json(body): Json<InteractionBody>,
+) -> impl IntoResponse {
+    let event = match body.action.as_str() {
+        "clicked" => webhooks::NOTIFICATION_CLICKED,
+        "closed" => webhooks::NOTIFICATION_CLOSED,
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": "action harus clicked atau closed" })),
+            );
+        }
     };
-    info!(endpoint = %endpoint, count, "subscription added");
-    (StatusCode::CREATED, Json(serde_json::json!({ "ok": true })))
+    let subscription = {
+        let subs = state.subscriptions.read().await;
+        subs.subscriptions
+            .iter()
+            .find(|s| s.endpoint == body.endpoint && owns_subscription(s, body.auth.as_deref()))
+            .map(|s| (s.id.clone(), s.app_id))
+    };
+    let Some((subscription_id, app_id)) = subscription else {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "ok": false, "message": "Subscription tidak dikenal" })),
+        );
+    };
+    state.webhooks.dispatch(
+        event,
+        app_id,
+        serde_json::json!({
+            "event": body.event,
+            "channel": body.channel,
+            "title": body.title,
+            "subscription_id": subscription_id,
+            "endpoint": body.endpoint
+        }),
+    );
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "ok": true })))
 }
 
 #[derive(Deserialize)]
@@ -63,16 +449,126 @@ pub struct NotifyPayload {
     /// URL ikon/logo notifikasi (opsional)
     #[serde(default)]
     pub icon: Option<String>,
+    /// Badge ikon aplikasi (APNs `aps.badge`).
+    #[serde(default)]
+    pub badge: Option<u32>,
+    /// Nama file suara (APNs `aps.sound`, default `default`).
+    #[serde(default)]
+    pub sound: Option<String>,
+    /// Batasi ke subscriber channel ini (nama konkret). Kosong = semua subscription.
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Target user tertentu (`user_id` saat subscribe); user tanpa push mendapat email.
+    /// Wajib dengan auth app.
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Jika payload melebihi batas Web Push: simpan payload lengkap dan kirim push ringkas
+    /// berisi `payload_id` (seperti `/trigger`), alih-alih memotong body. Default dari env
+    /// `PUSH_FETCH_ON_PUSH`.
+    #[serde(default)]
+    pub fetch_on_push: Option<bool>,
+    /// App pengirim yang terautentikasi; jika ada, hanya subscription app ini yang dikirimi.
+    #[serde(skip)]
+    pub app_id: Option<i32>,
+}
+
+/// App dari header `Authorization: Bearer <secret app>`. `Ok(None)` jika header tidak ada,
+/// `Err` jika secret tidak dikenal.
+async fn app_from_bearer(state: &AppState, headers: &HeaderMap) -> Result<Option<i32>, ()> {
+    let Some(value) = headers.get(axum::http::header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let secret = value
+        .to_str()
+        .ok()
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .ok_or(())?;
+    sqlx::query_as::<_, (i32,)>("SELECT id FROM keys WHERE secret = $1")
+        .bind(secret)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .map(|(id,)| Some(id))
+        .ok_or(())
 }
 
+/// Header `Idempotency-Key` opsional (lihat `crate::idempotency`). `users` butuh auth app
+/// (`Authorization: Bearer <secret>`).
 pub async fn notify(
     State(state): State<AppState>,
-    Json(payload): Json<NotifyPayload>,
+    headers: HeaderMap,
+    Json(request): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let subscriptions = {
+    let request_hash = idempotency::fingerprint(&request);
+    let mut payload: NotifyPayload = match serde_json::from_value(request) {
+        Ok(p) => p,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({ "ok": false, "message": e.to_string() })),
+            )
+                .into_response();
+        }
+    };
+    payload.app_id = match app_from_bearer(&state, &headers).await {
+        Ok(app_id) => app_id,
+        Err(()) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "ok": false, "message": "Secret app tidak valid" })),
+            )
+                .into_response();
+        }
+    };
+    if !payload.users.is_empty() && payload.app_id.is_none() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "ok": false, "message": "users butuh auth app (Authorization: Bearer <secret>)" })),
+        )
+            .into_response();
+    }
+    let app_id = payload.app_id;
+    idempotency::run(&state.db, "notify", app_id, &request_hash, &headers, || {
+        send_notify(&state, payload)
+    })
+    .await
+    .into_response()
+}
+
+pub async fn send_notify(state: &AppState, payload: NotifyPayload) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(message) = payload.channels.iter().try_for_each(|c| channel::validate_name(c)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    if let Err(message) = payload.users.iter().try_for_each(|u| recipients::validate_user_id(u)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    if let Some(app_id) = payload.app_id.filter(|_| !payload.users.is_empty()) {
+        return send_notify_users(state, app_id, payload).await;
+    }
+    let mut subscriptions = {
         let subs = state.subscriptions.read().await;
-        subs.all()
+        if payload.channels.is_empty() {
+            subs.all()
+        } else {
+            let filter = ChannelFilter {
+                any: payload.channels.clone(),
+                ..ChannelFilter::default()
+            };
+            subs.by_channel_filter(&filter, &[])
+        }
     };
+    if let Some(app_id) = payload.app_id {
+        subscriptions.retain(|s| s.app_id == Some(app_id));
+    }
     if subscriptions.is_empty() {
         info!("notify called but no subscriptions");
         return (
@@ -86,6 +582,57 @@ pub async fn notify(
         );
     }
 
+    let payload_json = match notify_push_payload(state, &payload).await {
+        Ok(json) => json,
+        Err(rejected) => return rejected,
+    };
+    let payload_text = payload_json.to_string();
+    let total = subscriptions.len();
+
+    let outcome = push_service::send_to_all(
+        state,
+        &subscriptions,
+        &payload_text,
+        None,
+        &payload.channels,
+    )
+    .await;
+
+    let id = state
+        .record_last_notification(&payload.title, &payload.body)
+        .await;
+    if let Err(e) = history::record(&state.db, None, &payload.title, &payload.body, 0, None).await {
+        warn!(error = %e, "record notification history failed");
+    }
+
+    info!(
+        sent = outcome.sent,
+        failed = outcome.failed,
+        deferred = outcome.deferred,
+        capped = outcome.capped,
+        total,
+        "notify completed"
+    );
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "ok": true,
+            "sent": outcome.sent,
+            "failed": outcome.failed,
+            "deferred": outcome.deferred,
+            "capped": outcome.capped,
+            "id": id,
+            "message": format!("Push terkirim ke {} subscription. Notifikasi akan muncul di browser yang sudah subscribe (browser harus tetap berjalan).", outcome.sent)
+        })),
+    )
+}
+
+/// Payload push `/notify` (title/body/icon). Jika tidak muat di batas Web Push: mode
+/// fetch-on-push menyimpan payload lengkap dan mengirim ringkasan, selain itu body dipotong.
+async fn notify_push_payload(
+    state: &AppState,
+    payload: &NotifyPayload,
+) -> Result<serde_json::Value, (StatusCode, Json<serde_json::Value>)> {
     let base_url = std::env::var("PUSH_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:3000".to_string());
     let icon_url = payload
         .icon
@@ -94,44 +641,231 @@ pub async fn notify(
         .cloned()
         .unwrap_or_else(|| format!("{}/static/icon-default.png", base_url.trim_end_matches('/')));
 
-    let payload_json = serde_json::json!({
+    let mut payload_json = serde_json::json!({
         "title": payload.title,
         "body": payload.body,
         "icon": icon_url
     });
-    let payload_bytes = payload_json.to_string().into_bytes();
-    let push_service = state.push_service.clone();
-    let total = subscriptions.len();
-
-    let (sent, failed) = push_service::send_to_all(
-        &push_service,
-        &subscriptions,
-        &payload_bytes,
-    )
-    .await;
+    if let Some(badge) = payload.badge {
+        payload_json["badge"] = badge.into();
+    }
+    if let Some(sound) = payload.sound.as_deref().filter(|s| !s.is_empty()) {
+        payload_json["sound"] = sound.into();
+    }
+    let mut size = crate::payload::push_size(&payload_json.to_string());
+    if !crate::payload::fits(size) && payload.fetch_on_push.unwrap_or_else(fetch_on_push_default) {
+        let payload_id = crate::payload::store(&state.db, &payload_json)
+            .await
+            .map_err(|e| {
+                warn!(error = %e, "store fetch-on-push payload failed");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan payload" })),
+                )
+            })?;
+        let mut preview = serde_json::json!({ "payload_id": payload_id });
+        for (field, value) in payload_json.as_object().into_iter().flatten() {
+            preview[field] = match value.as_str().filter(|_| field == "title" || field == "body") {
+                Some(text) => crate::payload::truncate(text, crate::payload::PREVIEW_CHARS).into(),
+                None => value.clone(),
+            };
+        }
+        payload_json = preview;
+        size = crate::payload::push_size(&payload_json.to_string());
+    }
+    // Kelebihan dihitung pada envelope (JSON di-escape dua kali), jadi satu byte body bisa
+    // bernilai lebih dari satu byte di envelope: potong berulang sampai muat.
+    let mut max_body = payload_json["body"].as_str().map_or(0, str::len);
+    let body = payload_json["body"].as_str().unwrap_or_default().to_string();
+    while !crate::payload::fits(size) && max_body > 0 {
+        let excess = size - crate::payload::MAX_PAYLOAD_SIZE;
+        max_body = max_body.saturating_sub(excess);
+        payload_json["body"] = crate::payload::truncate(&body, max_body).into();
+        size = crate::payload::push_size(&payload_json.to_string());
+    }
+    if !crate::payload::fits(size) {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(serde_json::json!({
+                "ok": false,
+                "message": crate::payload::too_large_message(size)
+            })),
+        ));
+    }
+    Ok(payload_json)
+}
 
-    let mut last = state.last_notification.write().await;
-    let next_id = last.as_ref().map(|n| n.id + 1).unwrap_or(1);
-    *last = Some(LastNotification {
-        id: next_id,
-        title: payload.title.clone(),
-        body: payload.body.clone(),
-    });
-    let id = next_id;
+/// `/notify` dengan `users` (app terautentikasi): push ke subscription milik tiap user di app
+/// ini; user tanpa push yang berhasil (tidak punya subscription, atau semua gagal) dikirimi
+/// email lewat SMTP relay di task terpisah. Isi email = title/body notifikasi. Hasil per user
+/// dicatat di `notification_deliveries`.
+async fn send_notify_users(
+    state: &AppState,
+    app_id: i32,
+    payload: NotifyPayload,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let payload_json = match notify_push_payload(state, &payload).await {
+        Ok(json) => json,
+        Err(rejected) => return rejected,
+    };
+    let payload_text = payload_json.to_string();
+    let history_id = match history::record(&state.db, None, &payload.title, &payload.body, 0, None).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!(error = %e, "record notification history failed");
+            None
+        }
+    };
+    let filter = ChannelFilter {
+        any: payload.channels.clone(),
+        ..ChannelFilter::default()
+    };
+    let (mut sent, mut failed, mut deferred, mut capped) = (0, 0, 0, 0);
+    let mut email_users = Vec::new();
+    for user in &payload.users {
+        let subscriptions = state
+            .subscriptions
+            .read()
+            .await
+            .for_user(app_id, user, &filter);
+        if subscriptions.is_empty() {
+            email_users.push(user.clone());
+            continue;
+        }
+        let outcome = push_service::send_to_all(
+            state,
+            &subscriptions,
+            &payload_text,
+            None,
+            &payload.channels,
+        )
+        .await;
+        sent += outcome.sent;
+        failed += outcome.failed;
+        deferred += outcome.deferred;
+        capped += outcome.capped;
+        // Push tertunda (quiet hours) tetap akan sampai dan push yang terkena frequency cap
+        // sengaja dilewati, jadi keduanya tidak perlu email.
+        if outcome.sent + outcome.deferred + outcome.capped == 0 {
+            email_users.push(user.clone());
+        }
+        let status = if outcome.sent > 0 {
+            recipients::STATUS_SENT
+        } else if outcome.deferred > 0 {
+            recipients::STATUS_DEFERRED
+        } else if outcome.capped > 0 {
+            recipients::STATUS_CAPPED
+        } else {
+            recipients::STATUS_FAILED
+        };
+        let detail = format!(
+            "{} terkirim, {} gagal, {} ditunda, {} di-cap",
+            outcome.sent, outcome.failed, outcome.deferred, outcome.capped
+        );
+        if let Some(history_id) = history_id {
+            if let Err(e) = recipients::record_delivery(
+                &state.db,
+                history_id,
+                user,
+                recipients::CHANNEL_PUSH,
+                status,
+                Some(&detail),
+            )
+            .await
+            {
+                warn!(error = %e, user = %user, "record delivery failed");
+            }
+        }
+    }
 
-    info!(sent, failed, total, "notify completed");
+    let id = state
+        .record_last_notification(&payload.title, &payload.body)
+        .await;
+    let email_queued = email_users.len();
+    info!(sent, failed, deferred, capped, email_queued, users = payload.users.len(), "notify users completed");
+    if !email_users.is_empty() {
+        tokio::spawn(email_fallback(state.clone(), app_id, history_id, email_users, payload));
+    }
     (
         StatusCode::OK,
         Json(serde_json::json!({
             "ok": true,
             "sent": sent,
             "failed": failed,
+            "deferred": deferred,
+            "capped": capped,
+            "email_queued": email_queued,
             "id": id,
-            "message": format!("Push terkirim ke {} subscription. Notifikasi akan muncul di browser yang sudah subscribe (browser harus tetap berjalan).", sent)
+            "history_id": history_id,
+            "message": format!("Push terkirim ke {} subscription, {} user akan dikirimi email.", sent, email_queued)
         })),
     )
 }
 
+/// Email fallback yang dikirim bersamaan.
+const EMAIL_CONCURRENCY: usize = 8;
+
+/// Email paralel ke user (maks `EMAIL_CONCURRENCY` sekaligus); hasil per user dicatat di
+/// `notification_deliveries`.
+async fn email_fallback(
+    state: AppState,
+    app_id: i32,
+    history_id: Option<i64>,
+    users: Vec<String>,
+    payload: NotifyPayload,
+) {
+    stream::iter(users)
+        .for_each_concurrent(EMAIL_CONCURRENCY, |user| {
+            let (state, payload) = (&state, &payload);
+            async move {
+                let (status, detail) = email_user(state, app_id, &user, payload).await;
+                let Some(history_id) = history_id else {
+                    return;
+                };
+                if let Err(e) = recipients::record_delivery(
+                    &state.db,
+                    history_id,
+                    &user,
+                    recipients::CHANNEL_EMAIL,
+                    status,
+                    detail.as_deref(),
+                )
+                .await
+                {
+                    warn!(error = %e, user = %user, "record delivery failed");
+                }
+            }
+        })
+        .await;
+}
+
+/// Kirim email ke user (jika relay dan alamatnya ada). Return (status, detail) untuk catatan.
+async fn email_user(
+    state: &AppState,
+    app_id: i32,
+    user: &str,
+    payload: &NotifyPayload,
+) -> (&'static str, Option<String>) {
+    if !state.mailer.enabled() {
+        return (recipients::STATUS_SKIPPED, Some("SMTP relay belum dikonfigurasi".to_string()));
+    }
+    let email = match recipients::email_for(&state.db, app_id, user).await {
+        Ok(Some(email)) => email,
+        Ok(None) => return (recipients::STATUS_SKIPPED, Some("User tanpa alamat email".to_string())),
+        Err(e) => return (recipients::STATUS_FAILED, Some(e.to_string())),
+    };
+    match state.mailer.send(&email, &payload.title, &payload.body).await {
+        Ok(()) => {
+            info!(user = %user, "notification emailed");
+            (recipients::STATUS_SENT, None)
+        }
+        Err(e) => {
+            warn!(user = %user, error = %e, "email fallback failed");
+            (recipients::STATUS_FAILED, Some(e.to_string()))
+        }
+    }
+}
+
 pub async fn notify_last(State(state): State<AppState>) -> impl IntoResponse {
     let last = state.last_notification.read().await;
     let response = match last.as_ref() {
@@ -141,6 +875,51 @@ pub async fn notify_last(State(state): State<AppState>) -> impl IntoResponse {
     (StatusCode::OK, Json(response))
 }
 
+#[derive(Deserialize)]
+pub struct PayloadQuery {
+    /// Public key app yang dipin `sw.js`; payload ditandatangani dengan key app ini.
+    #[serde(default)]
+    pub key: Option<String>,
+}
+
+/// Payload lengkap fetch-on-push, diambil `sw.js` saat push hanya membawa `payload_id`.
+/// ID acak 128-bit berfungsi sebagai capability, jadi endpoint ini tanpa auth.
+pub async fn payload_get(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<PayloadQuery>,
+) -> impl IntoResponse {
+    match crate::payload::fetch(&state.db, &id).await {
+        // Ditandatangani juga supaya sw.js bisa memverifikasi payload lengkapnya.
+        Ok(Some(payload)) => {
+            let app_id = match query.key.as_deref() {
+                Some(public_key) => sqlx::query_as::<_, (i32,)>("SELECT id FROM keys WHERE public_key = $1")
+                    .bind(public_key)
+                    .fetch_optional(&state.db)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|(id,)| id),
+                None => None,
+            };
+            let sealed = state.push_service.seal_for(app_id, &payload.to_string()).await;
+            let envelope = serde_json::from_str(&sealed).unwrap_or(payload);
+            (StatusCode::OK, Json(envelope))
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Payload tidak ditemukan atau kedaluwarsa" })),
+        ),
+        Err(e) => {
+            warn!(error = %e, "fetch payload failed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal mengambil payload" })),
+            )
+        }
+    }
+}
+
 // --- Trigger (gaya Pusher) ---
 
 #[derive(Deserialize)]
@@ -148,78 +927,694 @@ pub struct TriggerBody {
     /// Channel(s) tujuan. Kosong = kirim ke semua subscription (broadcast).
     #[serde(default)]
     pub channels: Vec<String>,
+    /// Penerima harus berlangganan semua channel ini (AND).
+    #[serde(default)]
+    pub channels_all: Vec<String>,
+    /// Penerima yang berlangganan salah satu channel ini dilewati.
+    #[serde(default)]
+    pub exclude_channels: Vec<String>,
+    /// Penerima yang dilewati (mis. pengirim sendiri, gaya `socket_id` Pusher): ID atau
+    /// endpoint subscription, atau socket ID koneksi realtime.
+    #[serde(default)]
+    pub exclude: Vec<String>,
     /// Nama event (wajib).
     pub event: String,
     /// Data payload (object bebas). Untuk notifikasi OS bisa pakai title/body di dalam data.
     #[serde(default)]
     pub data: serde_json::Value,
-}
+    /// Jika payload melebihi batas Web Push: simpan payload dan kirim push ringkas berisi
+    /// `payload_id` (lihat `crate::payload`). Default dari env `PUSH_FETCH_ON_PUSH`.
+    #[serde(default)]
+    pub fetch_on_push: Option<bool>,
+    /// Header `Urgency` Web Push: `very-low`, `low`, `normal`, `high`.
+    #[serde(default)]
+    pub urgency: Option<Urgency>,
+    /// App pengirim yang terautentikasi (API Pusher); jika ada, hanya subscription dan koneksi
+    /// realtime app ini yang dikirimi.
+    #[serde(skip)]
+    pub app_id: Option<i32>,
+}
 
-pub async fn trigger(
-    State(state): State<AppState>,
-    Json(body): Json<TriggerBody>,
-) -> impl IntoResponse {
+/// Hasil pengiriman satu trigger.
+pub struct TriggerOutcome {
+    pub sent: usize,
+    pub failed: usize,
+    /// Ditunda karena quiet hours.
+    pub deferred: usize,
+    /// Dilewati karena frequency cap.
+    pub capped: usize,
+    /// Jumlah subscription Web Push yang dituju.
+    pub total: usize,
+}
+
+/// Trigger ditolak: status HTTP + pesan.
+pub struct TriggerError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl From<String> for TriggerError {
+    fn from(message: String) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+}
+
+fn channel_label(body: &TriggerBody) -> &str {
+    if body.channels.is_empty() && body.channels_all.is_empty() {
+        "broadcast"
+    } else if body.channels.len() == 1 && body.channels_all.is_empty() {
+        body.channels[0].as_str()
+    } else if body.channels.is_empty() && body.channels_all.len() == 1 {
+        body.channels_all[0].as_str()
+    } else {
+        "multi"
+    }
+}
+
+fn trigger_payload(body: &TriggerBody) -> serde_json::Value {
+    serde_json::json!({
+        "event": body.event,
+        "channel": channel_label(body),
+        "data": body.data
+    })
+}
+
+fn fetch_on_push_default() -> bool {
+    std::env::var("PUSH_FETCH_ON_PUSH").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+fn fetch_on_push_enabled(body: &TriggerBody) -> bool {
+    body.fetch_on_push.unwrap_or_else(fetch_on_push_default)
+}
+
+/// Push ringkas fetch-on-push: hanya `payload_id` + title/body terpotong untuk fallback.
+fn fetch_on_push_preview(payload: &serde_json::Value, payload_id: &str) -> serde_json::Value {
+    let mut preview = serde_json::Map::new();
+    for field in ["title", "body"] {
+        if let Some(text) = payload["data"].get(field).and_then(|v| v.as_str()) {
+            preview.insert(
+                field.to_string(),
+                crate::payload::truncate(text, crate::payload::PREVIEW_CHARS).into(),
+            );
+        }
+    }
+    serde_json::json!({
+        "event": payload["event"],
+        "channel": payload["channel"],
+        "payload_id": payload_id,
+        "data": preview
+    })
+}
+
+const INTERNAL_EVENT_PREFIX: &str = "pusher_internal:";
+
+/// Validasi trigger tanpa mengirim apa pun.
+pub fn validate_trigger(body: &TriggerBody) -> Result<(), TriggerError> {
     if body.event.trim().is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "ok": false,
-                "message": "event wajib diisi"
-            })),
-        );
+        return Err("event wajib diisi".to_string().into());
+    }
+    // Event internal (member_added, subscription_succeeded, ...) hanya dibuat server.
+    if body.event.starts_with(INTERNAL_EVENT_PREFIX) {
+        return Err(format!("event {}* tidak boleh di-trigger", INTERNAL_EVENT_PREFIX).into());
+    }
+    body.channels
+        .iter()
+        .chain(&body.channels_all)
+        .chain(&body.exclude_channels)
+        .try_for_each(|c| channel::validate_name(c))?;
+    // Channel private/presence hanya lewat trigger yang diautentikasi app (API Pusher, dll.).
+    if body.app_id.is_none() {
+        if let Some(c) = body
+            .channels
+            .iter()
+            .chain(&body.channels_all)
+            .find(|c| presence::requires_auth(c))
+        {
+            return Err(TriggerError {
+                status: StatusCode::FORBIDDEN,
+                message: format!("channel {} butuh auth app", c),
+            });
+        }
+    }
+    let payload = trigger_payload(body);
+    let mut size = crate::payload::push_size(&payload.to_string());
+    if !crate::payload::fits(size) && fetch_on_push_enabled(body) {
+        // Push ringkas juga harus muat (event / nama channel tidak ikut dipotong).
+        let placeholder_id = "0".repeat(crate::payload::PAYLOAD_ID_LEN);
+        size = crate::payload::push_size(&fetch_on_push_preview(&payload, &placeholder_id).to_string());
+    }
+    if !crate::payload::fits(size) {
+        return Err(TriggerError {
+            status: StatusCode::PAYLOAD_TOO_LARGE,
+            message: crate::payload::too_large_message(size),
+        });
+    }
+    Ok(())
+}
+
+/// Jalur publish bersama (`/trigger`, API Pusher, dll.): validasi, kirim ke koneksi
+/// realtime, lalu Web Push.
+pub async fn publish_trigger(
+    state: &AppState,
+    body: &TriggerBody,
+) -> Result<TriggerOutcome, TriggerError> {
+    validate_trigger(body)?;
+    {
+        // Dicatat per pola channel subscription yang cocok (seperti di `GET /api/channels`),
+        // jadi /trigger tanpa auth tidak bisa menumbuhkan map ini dengan nama sembarang.
+        let now = chrono::Utc::now();
+        let subs = state.subscriptions.read().await;
+        let mut last = state.channel_last_triggered.write().await;
+        for ch in body.channels.iter().chain(&body.channels_all) {
+            for pattern in subs.matching_channels(ch) {
+                last.insert(pattern, now);
+            }
+        }
     }
-    let subscriptions = {
+    let channel_label = channel_label(body);
+    let payload_json = trigger_payload(body);
+    let payload_text = payload_json.to_string();
+    let target_channels: Vec<String> = body.channels.iter().chain(&body.channels_all).cloned().collect();
+    // Sink channel (Slack / Discord / Teams) selalu menerima payload lengkap.
+    sinks::dispatch(
+        state,
+        body.app_id,
+        target_channels.clone(),
+        payload_text.clone(),
+        body.urgency,
+    );
+    // Koneksi realtime tidak dibatasi ukuran push, jadi selalu terima payload lengkap.
+    let push_payload = if crate::payload::fits(crate::payload::push_size(&payload_text)) {
+        payload_text
+    } else {
+        let payload_id = crate::payload::store(&state.db, &payload_json)
+            .await
+            .map_err(|e| {
+                warn!(error = %e, "store fetch-on-push payload failed");
+                TriggerError {
+                    status: StatusCode::INTERNAL_SERVER_ERROR,
+                    message: "Gagal menyimpan payload".to_string(),
+                }
+            })?;
+        fetch_on_push_preview(&payload_json, &payload_id).to_string()
+    };
+    let event = RealtimeEvent {
+        event: body.event.clone(),
+        channel: channel_label.to_string(),
+        filter: ChannelFilter {
+            any: body.channels.clone(),
+            all: body.channels_all.clone(),
+            exclude: body.exclude_channels.clone(),
+        },
+        data: body.data.clone(),
+        exclude: body.exclude.clone(),
+        app_id: body.app_id,
+    };
+    let mut subscriptions = {
         let subs = state.subscriptions.read().await;
-        subs.by_channels(&body.channels)
+        subs.by_channel_filter(&event.filter, &body.exclude)
     };
+    if let Some(app_id) = body.app_id {
+        subscriptions.retain(|s| s.app_id == Some(app_id));
+    }
+    state.realtime.publish(event);
     if subscriptions.is_empty() {
         info!("trigger called but no subscriptions for channels");
+        return Ok(TriggerOutcome {
+            sent: 0,
+            failed: 0,
+            deferred: 0,
+            capped: 0,
+            total: 0,
+        });
+    }
+
+    let total = subscriptions.len();
+
+    let outcome = push_service::send_to_all(
+        state,
+        &subscriptions,
+        &push_payload,
+        body.urgency,
+        &target_channels,
+    )
+    .await;
+
+    info!(
+        event = %body.event,
+        channel = %channel_label,
+        sent = outcome.sent,
+        failed = outcome.failed,
+        deferred = outcome.deferred,
+        capped = outcome.capped,
+        total,
+        "trigger completed"
+    );
+    Ok(TriggerOutcome {
+        sent: outcome.sent,
+        failed: outcome.failed,
+        deferred: outcome.deferred,
+        capped: outcome.capped,
+        total,
+    })
+}
+
+/// Header `Idempotency-Key` opsional (lihat `crate::idempotency`).
+pub async fn trigger(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let request_hash = idempotency::fingerprint(&request);
+    let body: TriggerBody = match serde_json::from_value(request) {
+        Ok(body) => body,
+        Err(e) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({ "ok": false, "message": e.to_string() })),
+            );
+        }
+    };
+    idempotency::run(&state.db, "trigger", None, &request_hash, &headers, || {
+        send_trigger(&state, body)
+    })
+    .await
+}
+
+async fn send_trigger(state: &AppState, body: TriggerBody) -> (StatusCode, Json<serde_json::Value>) {
+    let outcome = match publish_trigger(state, &body).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            return (
+                e.status,
+                Json(serde_json::json!({ "ok": false, "message": e.message })),
+            );
+        }
+    };
+    if outcome.total == 0 {
         return (
             StatusCode::OK,
             Json(serde_json::json!({
                 "ok": true,
                 "sent": 0,
                 "failed": 0,
+                "deferred": 0,
+                "capped": 0,
                 "message": "No subscriptions for channel(s)"
             })),
         );
     }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "ok": true,
+            "sent": outcome.sent,
+            "failed": outcome.failed,
+            "deferred": outcome.deferred,
+            "capped": outcome.capped,
+            "message": format!("Event '{}' terkirim ke {} subscription.", body.event, outcome.sent)
+        })),
+    )
+}
 
-    let channel_label = if body.channels.is_empty() {
-        "broadcast"
-    } else if body.channels.len() == 1 {
-        body.channels[0].as_str()
-    } else {
-        "multi"
-    };
-    let payload_json = serde_json::json!({
-        "event": body.event,
-        "channel": channel_label,
-        "data": body.data
-    });
-    let payload_bytes = payload_json.to_string().into_bytes();
-    let push_service = state.push_service.clone();
-    let total = subscriptions.len();
+/// Jumlah maksimum item per `/trigger/batch`.
+const MAX_TRIGGER_BATCH: usize = 100;
 
-    let (sent, failed) = push_service::send_to_all(
-        &push_service,
-        &subscriptions,
-        &payload_bytes,
-    )
-    .await;
+/// `POST /trigger/batch` — array item berbentuk `TriggerBody`. Semua item divalidasi dulu;
+/// jika ada yang tidak valid, tidak ada yang dikirim.
+pub async fn trigger_batch(
+    State(state): State<AppState>,
+    Json(items): Json<Vec<TriggerBody>>,
+) -> impl IntoResponse {
+    if items.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": "batch tidak boleh kosong" })),
+        );
+    }
+    if items.len() > MAX_TRIGGER_BATCH {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "ok": false,
+                "message": format!("maksimal {} item per batch", MAX_TRIGGER_BATCH)
+            })),
+        );
+    }
+    let mut status = StatusCode::BAD_REQUEST;
+    let errors: Vec<serde_json::Value> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            validate_trigger(item).err().map(|e| {
+                if e.status == StatusCode::PAYLOAD_TOO_LARGE {
+                    status = e.status;
+                }
+                serde_json::json!({ "index": index, "message": e.message })
+            })
+        })
+        .collect();
+    if !errors.is_empty() {
+        return (
+            status,
+            Json(serde_json::json!({
+                "ok": false,
+                "message": "batch tidak valid",
+                "errors": errors
+            })),
+        );
+    }
 
-    info!(event = %body.event, channel = %channel_label, sent, failed, total, "trigger completed");
+    let mut results = Vec::with_capacity(items.len());
+    let (mut sent, mut failed, mut deferred, mut capped) = (0, 0, 0, 0);
+    for (index, item) in items.iter().enumerate() {
+        let result = match publish_trigger(&state, item).await {
+            Ok(outcome) => {
+                sent += outcome.sent;
+                failed += outcome.failed;
+                deferred += outcome.deferred;
+                capped += outcome.capped;
+                serde_json::json!({
+                    "index": index,
+                    "ok": true,
+                    "event": item.event,
+                    "sent": outcome.sent,
+                    "failed": outcome.failed,
+                    "deferred": outcome.deferred,
+                    "capped": outcome.capped
+                })
+            }
+            Err(e) => serde_json::json!({
+                "index": index,
+                "ok": false,
+                "event": item.event,
+                "message": e.message
+            }),
+        };
+        results.push(result);
+    }
+    info!(items = items.len(), sent, failed, deferred, capped, "trigger batch completed");
     (
         StatusCode::OK,
         Json(serde_json::json!({
             "ok": true,
             "sent": sent,
             "failed": failed,
-            "message": format!("Event '{}' terkirim ke {} subscription.", body.event, sent)
+            "deferred": deferred,
+            "capped": capped,
+            "results": results
         })),
     )
 }
 
+// --- Stream (SSE, fallback realtime jika Web Push tidak tersedia) ---
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    /// Daftar channel publik dipisah koma (boleh wildcard). Kosong = channel "default".
+    /// Channel private/presence di-subscribe lewat `POST /stream/subscribe`.
+    #[serde(default)]
+    pub channels: String,
+    /// Public key app; wajib untuk channel private/presence.
+    #[serde(default)]
+    pub app: Option<String>,
+}
+
+/// Secret HMAC (kolom `secret`) untuk public key app, None jika app tidak ada.
+/// (id, secret) app dengan public key ini.
+async fn key_app(state: &AppState, public_key: &str) -> Option<(i32, String)> {
+    sqlx::query_as("SELECT id, secret FROM keys WHERE public_key = $1")
+        .bind(public_key)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Melepas registrasi koneksi SSE (dan membership presence-nya) saat stream ditutup.
+struct StreamGuard {
+    state: AppState,
+    socket_id: String,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        // Presence hanya bisa di-join oleh stream yang dibuka dengan app.
+        let Some((Some(app_id), channels)) = self.state.streams.unregister(&self.socket_id) else {
+            return;
+        };
+        drop(PresenceGuard {
+            registry: self.state.presence.clone(),
+            hub: self.state.realtime.clone(),
+            app_id,
+            socket_id: std::mem::take(&mut self.socket_id),
+            channels: channels
+                .into_iter()
+                .filter(|c| c.starts_with(presence::PRESENCE_PREFIX))
+                .collect(),
+        });
+    }
+}
+
+/// Socket ID selalu dibuat server dan dikirim di event `connection_established`.
+pub async fn stream(
+    State(state): State<AppState>,
+    Query(query): Query<StreamQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, impl IntoResponse> {
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        )
+    };
+    let mut channels: Vec<String> = query
+        .channels
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect();
+    if channels.is_empty() {
+        channels.push("default".to_string());
+    }
+    if channels.iter().any(|c| presence::requires_auth(c)) {
+        return Err(bad_request(
+            "channel private/presence di-subscribe lewat POST /stream/subscribe".to_string(),
+        ));
+    }
+    if let Err(message) = channels.iter().try_for_each(|c| channel::validate_pattern(c)) {
+        return Err(bad_request(message));
+    }
+    let app_id = match query.app.as_deref().filter(|k| !k.is_empty()) {
+        Some(public_key) => {
+            let row: Option<(i32,)> = sqlx::query_as("SELECT id FROM keys WHERE public_key = $1")
+                .bind(public_key)
+                .fetch_optional(&state.db)
+                .await
+                .ok()
+                .flatten();
+            match row {
+                Some((id,)) => Some(id),
+                None => {
+                    return Err((
+                        StatusCode::NOT_FOUND,
+                        Json(serde_json::json!({ "ok": false, "message": "App key tidak ditemukan" })),
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+    let socket_id = websocket::generate_socket_id();
+    state.streams.register(&socket_id, app_id);
+    let guard = StreamGuard {
+        state: state.clone(),
+        socket_id: socket_id.clone(),
+    };
+    let initial = Event::default()
+        .event("connection_established")
+        .data(serde_json::json!({ "socket_id": socket_id }).to_string());
+
+    info!(channels = ?channels, socket_id = %socket_id, "stream connected");
+    let rx = state.realtime.subscribe();
+    let initial = stream::once(async { Ok(initial) });
+    let events = stream::unfold((rx, channels, guard), move |(mut rx, channels, guard)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(ev)
+                    if ev.is_for(&guard.socket_id, app_id, &channels)
+                        || guard.state.streams.accepts(&guard.socket_id, &ev) =>
+                {
+                    let event = Event::default().data(ev.payload().to_string());
+                    return Some((Ok(event), (rx, channels, guard)));
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "stream lagged, events dropped");
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Ok(Sse::new(initial.chain(events)).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Deserialize)]
+pub struct StreamSubscribeBody {
+    /// Socket ID dari event `connection_established`.
+    pub socket_id: String,
+    pub channel: String,
+    /// Auth channel: `<public_key>:<signature>` atas `<socket_id>:<channel>[:<channel_data>]`.
+    pub auth: String,
+    /// Data member presence: `{"user_id": ..., "user_info": {...}}`.
+    #[serde(default)]
+    pub channel_data: Option<String>,
+}
+
+/// Subscribe koneksi SSE yang sedang terbuka ke channel private/presence. App diambil dari
+/// koneksi (`?app=` saat `/stream`), bukan dari string auth.
+pub async fn stream_subscribe(
+    State(state): State<AppState>,
+    Json(body): Json<StreamSubscribeBody>,
+) -> impl IntoResponse {
+    let channel_name = body.channel.as_str();
+    if !presence::requires_auth(channel_name) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": "hanya untuk channel private/presence" })),
+        );
+    }
+    if let Err(message) = channel::validate_name(channel_name) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let app_id = match state.streams.app(&body.socket_id) {
+        Some(Some(app_id)) => app_id,
+        Some(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": "stream dibuka tanpa app" })),
+            );
+        }
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "socket_id tidak terhubung" })),
+            );
+        }
+    };
+    let app: Option<(String, String)> =
+        sqlx::query_as("SELECT public_key, secret FROM keys WHERE id = $1")
+            .bind(app_id)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+    let is_presence = channel_name.starts_with(presence::PRESENCE_PREFIX);
+    let channel_data = if is_presence { body.channel_data.as_deref() } else { None };
+    let authorized = app.is_some_and(|(public_key, secret)| {
+        signature::verify_channel_auth(
+            &public_key,
+            &secret,
+            &body.auth,
+            &body.socket_id,
+            channel_name,
+            channel_data,
+        )
+    });
+    if !authorized {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({ "ok": false, "message": "auth signature tidak valid" })),
+        );
+    }
+    let member = if is_presence {
+        match presence::parse_channel_data(channel_data.unwrap_or("")) {
+            Ok(member) => Some(member),
+            Err(message) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({ "ok": false, "message": message })),
+                );
+            }
+        }
+    } else {
+        None
+    };
+    if !state.streams.add_channel(&body.socket_id, channel_name) {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "socket_id tidak terhubung" })),
+        );
+    }
+    let mut succeeded = serde_json::json!({});
+    if let Some(member) = member {
+        state.presence.join_and_announce(
+            &state.realtime,
+            app_id,
+            channel_name,
+            &body.socket_id,
+            &member,
+        );
+        // Stream bisa tertutup di antara add_channel dan join; jangan tinggalkan member.
+        if state.streams.app(&body.socket_id).is_none() {
+            state.presence.leave_and_announce(
+                &state.realtime,
+                app_id,
+                channel_name,
+                &body.socket_id,
+            );
+        }
+        succeeded = state.presence.subscription_data(app_id, channel_name);
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "ok": true, "channel": channel_name, "data": succeeded })),
+    )
+}
+
+// --- WebSocket (protokol Pusher) ---
+
+/// `GET /app/:key` — endpoint pusher-js. `key` = public key dari tabel keys.
+pub async fn pusher_ws(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let app = key_app(&state, &key).await;
+    ws.on_upgrade(move |mut socket| async move {
+        let Some((id, secret)) = app else {
+            warn!("websocket rejected: unknown app key");
+            let _ = socket
+                .send(websocket::error_frame(
+                    websocket::ERROR_APP_NOT_FOUND,
+                    "App key tidak ditemukan",
+                ))
+                .await;
+            // Protokol Pusher: kode 4000-4099 berarti klien tidak boleh reconnect.
+            let _ = socket
+                .send(Message::Close(Some(CloseFrame {
+                    code: websocket::ERROR_APP_NOT_FOUND,
+                    reason: "App key tidak ditemukan".into(),
+                })))
+                .await;
+            return;
+        };
+        let app = websocket::App {
+            id,
+            public_key: key,
+            secret,
+        };
+        websocket::serve(socket, state, app).await
+    })
+}
+
 // --- Auth ---
 
 #[derive(Deserialize)]
@@ -406,7 +1801,7 @@ pub async fn keys_list(
     State(state): State<AppState>,
     AuthUser(_): AuthUser,
 ) -> impl IntoResponse {
-    let rows: Vec<KeyRow> = sqlx::query_as("SELECT id, name, key, public_key, domain, created_at FROM keys ORDER BY id")
+    let rows: Vec<KeyRow> = sqlx::query_as("SELECT id, name, public_key, secret, domain, created_at FROM keys ORDER BY id")
         .fetch_all(&state.db)
         .await
         .unwrap_or_default();
@@ -437,11 +1832,12 @@ pub async fn key_create(
         }
     };
     let row = sqlx::query_as::<_, KeyRow>(
-        "INSERT INTO keys (name, key, public_key, domain) VALUES ($1, $2, $3, $4) RETURNING id, name, key, public_key, domain, created_at",
+        "INSERT INTO keys (name, key, public_key, secret, domain) VALUES ($1, $2, $3, $4, $5) RETURNING id, name, public_key, secret, domain, created_at",
     )
     .bind(name)
     .bind(&key)
     .bind(&public_key)
+    .bind(webhooks::generate_secret())
     .bind(domain)
     .fetch_one(&state.db)
     .await;
@@ -466,7 +1862,7 @@ pub async fn key_update(
     Path(id): Path<i32>,
     Json(body): Json<UpdateKeyBody>,
 ) -> impl IntoResponse {
-    let existing: Option<KeyRow> = sqlx::query_as("SELECT id, name, key, public_key, domain, created_at FROM keys WHERE id = $1")
+    let existing: Option<KeyRow> = sqlx::query_as("SELECT id, name, public_key, secret, domain, created_at FROM keys WHERE id = $1")
         .bind(id)
         .fetch_optional(&state.db)
         .await
@@ -527,16 +1923,17 @@ pub async fn key_regenerate(
             );
         }
     };
-    let result = sqlx::query("UPDATE keys SET key = $1, public_key = $2 WHERE id = $3")
+    let result = sqlx::query("UPDATE keys SET key = $1, public_key = $2, secret = $3 WHERE id = $4")
         .bind(&key)
         .bind(&public_key)
+        .bind(webhooks::generate_secret())
         .bind(id)
         .execute(&state.db)
         .await;
     match result {
         Ok(r) if r.rows_affected() > 0 => (
             StatusCode::OK,
-            Json(serde_json::json!({ "ok": true, "message": "Key dan Public Key berhasil diregenerate" })),
+            Json(serde_json::json!({ "ok": true, "message": "Key, Public Key, dan Secret berhasil diregenerate" })),
         ),
         _ => (
             StatusCode::NOT_FOUND,
@@ -565,3 +1962,1289 @@ pub async fn key_delete(
         ),
     }
 }
+
+/// Simpan service account Firebase (JSON dari Google Cloud console) untuk transport FCM app.
+pub async fn key_fcm_put(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let account = match crate::fcm::parse_service_account(&body) {
+        Ok(a) => a,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+    };
+    let result = sqlx::query(
+        "INSERT INTO fcm_credentials (key_id, service_account) VALUES ($1, $2) ON CONFLICT (key_id) DO UPDATE SET service_account = EXCLUDED.service_account, updated_at = NOW()",
+    )
+    .bind(id)
+    .bind(body.to_string())
+    .execute(&state.db)
+    .await;
+    match result {
+        Ok(_) => {
+            state.push_service.fcm().forget(id).await;
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "ok": true,
+                    "project_id": account.project_id,
+                    "client_email": account.client_email
+                })),
+            )
+        }
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "save fcm credentials");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan service account" })),
+            )
+        }
+    }
+}
+
+pub async fn key_fcm_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM fcm_credentials WHERE key_id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    state.push_service.fcm().forget(id).await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Service account FCM tidak ditemukan" })),
+        ),
+    }
+}
+
+/// Simpan kredensial APNs token-based (`.p8`) untuk transport iOS app.
+pub async fn key_apns_put(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<ApnsCredentials>,
+) -> impl IntoResponse {
+    if let Err(message) = crate::apns::validate_credentials(&body) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let result = sqlx::query(
+        "INSERT INTO apns_credentials (key_id, team_id, auth_key_id, private_key, topic, sandbox) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (key_id) DO UPDATE SET team_id = EXCLUDED.team_id, auth_key_id = EXCLUDED.auth_key_id, private_key = EXCLUDED.private_key, topic = EXCLUDED.topic, sandbox = EXCLUDED.sandbox, updated_at = NOW()",
+    )
+    .bind(id)
+    .bind(body.team_id.trim())
+    .bind(body.key_id.trim())
+    .bind(&body.private_key)
+    .bind(body.topic.trim())
+    .bind(body.sandbox)
+    .execute(&state.db)
+    .await;
+    match result {
+        Ok(_) => {
+            state.push_service.apns().forget(id).await;
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "ok": true,
+                    "team_id": body.team_id.trim(),
+                    "key_id": body.key_id.trim(),
+                    "topic": body.topic.trim(),
+                    "sandbox": body.sandbox
+                })),
+            )
+        }
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "save apns credentials");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan kredensial APNs" })),
+            )
+        }
+    }
+}
+
+pub async fn key_apns_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM apns_credentials WHERE key_id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    state.push_service.apns().forget(id).await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Kredensial APNs tidak ditemukan" })),
+        ),
+    }
+}
+
+/// Quiet hours app: berlaku untuk semua subscription dengan `app_id` ini.
+pub async fn key_quiet_hours_put(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<QuietHours>,
+) -> impl IntoResponse {
+    if !body.enabled() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": "start dan end tidak boleh sama" })),
+        );
+    }
+    if let Err(message) = quiet_hours::validate(&state.db, &body).await {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let result = sqlx::query(
+        "INSERT INTO quiet_hours (key_id, start_time, end_time, timezone) VALUES ($1, $2, $3, $4) ON CONFLICT (key_id) DO UPDATE SET start_time = EXCLUDED.start_time, end_time = EXCLUDED.end_time, timezone = EXCLUDED.timezone, updated_at = NOW()",
+    )
+    .bind(id)
+    .bind(body.start)
+    .bind(body.end)
+    .bind(&body.timezone)
+    .execute(&state.db)
+    .await;
+    match result {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "quiet_hours": body })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "save quiet hours");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan quiet hours" })),
+            )
+        }
+    }
+}
+
+pub async fn key_quiet_hours_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM quiet_hours WHERE key_id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Quiet hours tidak ditemukan" })),
+        ),
+    }
+}
+
+// --- Webhook subscriptions per app (protected) ---
+
+#[derive(Deserialize)]
+pub struct CreateWebhookSubscriptionBody {
+    pub url: String,
+    /// Format body: `json` (default), `slack`, `discord`, `teams`.
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Kosong = channel "default".
+    #[serde(default)]
+    pub channels: Vec<String>,
+}
+
+pub async fn key_webhook_subscriptions_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let subs = state.subscriptions.read().await;
+    let rows: Vec<serde_json::Value> = subs
+        .subscriptions
+        .iter()
+        .filter(|s| s.kind == SubscriptionKind::Webhook && s.app_id == Some(id))
+        .map(|s| {
+            serde_json::json!({
+                "id": s.id,
+                "url": s.endpoint,
+                "format": s.format.unwrap_or_default(),
+                "channels": s.channels
+            })
+        })
+        .collect();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+/// Subscription `type: "webhook"` untuk app `id`; URL harus mengarah ke alamat publik.
+pub async fn key_webhook_subscription_create(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<CreateWebhookSubscriptionBody>,
+) -> impl IntoResponse {
+    if let Err(message) = validate_push_channels(&body.channels) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let url = body.url.trim().to_string();
+    if let Err(message) = webhooks::resolve_public(&url).await {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let key_exists: Option<(i32,)> = sqlx::query_as("SELECT id FROM keys WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten();
+    if key_exists.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        );
+    }
+    let (sub_id, channels) = {
+        let mut subs = state.subscriptions.write().await;
+        if subs
+            .subscriptions
+            .iter()
+            .any(|s| s.endpoint == url && s.app_id != Some(id))
+        {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "ok": false, "message": "URL sudah dipakai subscription lain" })),
+            );
+        }
+        let Some(sub_id) = subs.add(StoredSubscription {
+            id: String::new(),
+            kind: SubscriptionKind::Webhook,
+            endpoint: url.clone(),
+            keys: SubscriptionKeys::default(),
+            app_id: Some(id),
+            user_id: None,
+            format: Some(body.format),
+            quiet_hours: None,
+            channels: body.channels,
+        }) else {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "ok": false, "message": "URL sudah dipakai subscription lain" })),
+            );
+        };
+        let channels = subs
+            .subscriptions
+            .iter()
+            .find(|s| s.id == sub_id)
+            .map(|s| s.channels.clone())
+            .unwrap_or_default();
+        let to_save = subs.clone();
+        if let Err(e) = save_subscriptions(&to_save).await {
+            warn!(error = %e, "failed to persist subscriptions");
+        }
+        (sub_id, channels)
+    };
+    info!(url = %url, app_id = id, "webhook subscription added");
+    state.webhooks.dispatch(
+        webhooks::SUBSCRIPTION_CREATED,
+        Some(id),
+        serde_json::json!({ "id": sub_id, "endpoint": url, "channels": channels }),
+    );
+    (StatusCode::CREATED, Json(serde_json::json!({ "ok": true, "id": sub_id })))
+}
+
+pub async fn key_webhook_subscription_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path((id, sub_id)): Path<(i32, String)>,
+) -> impl IntoResponse {
+    let removed = {
+        let mut subs = state.subscriptions.write().await;
+        let removed = subs.remove_where(|s| {
+            s.id == sub_id && s.kind == SubscriptionKind::Webhook && s.app_id == Some(id)
+        });
+        if removed.is_some() {
+            let to_save = subs.clone();
+            if let Err(e) = save_subscriptions(&to_save).await {
+                warn!(error = %e, "failed to persist subscriptions");
+            }
+        }
+        removed
+    };
+    let Some(s) = removed else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Subscription tidak ditemukan" })),
+        );
+    };
+    state.webhooks.dispatch(
+        webhooks::SUBSCRIPTION_DELETED,
+        s.app_id,
+        serde_json::json!({ "id": s.id, "endpoint": s.endpoint, "channels": s.channels }),
+    );
+    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+}
+
+// --- Recipients (protected) ---
+
+pub async fn recipients_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(key_id): Path<i32>,
+) -> impl IntoResponse {
+    let rows: Vec<RecipientRow> = sqlx::query_as(
+        "SELECT key_id, user_id, email, created_at FROM recipients WHERE key_id = $1 ORDER BY user_id",
+    )
+    .bind(key_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+/// Set alamat email fallback untuk user di app `key_id`.
+pub async fn recipient_put(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path((key_id, user_id)): Path<(i32, String)>,
+    Json(body): Json<UpsertRecipientBody>,
+) -> impl IntoResponse {
+    let email = body.email.trim();
+    if let Err(message) =
+        recipients::validate_user_id(&user_id).and_then(|_| recipients::validate_email(email))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let row = sqlx::query_as::<_, RecipientRow>(
+        "INSERT INTO recipients (key_id, user_id, email) VALUES ($1, $2, $3) ON CONFLICT (key_id, user_id) DO UPDATE SET email = EXCLUDED.email RETURNING key_id, user_id, email, created_at",
+    )
+    .bind(key_id)
+    .bind(&user_id)
+    .bind(email)
+    .fetch_one(&state.db)
+    .await;
+    match row {
+        Ok(r) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "recipient": r })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "upsert recipient");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan penerima" })),
+            )
+        }
+    }
+}
+
+pub async fn recipient_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path((key_id, user_id)): Path<(i32, String)>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM recipients WHERE key_id = $1 AND user_id = $2")
+        .bind(key_id)
+        .bind(&user_id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Penerima tidak ditemukan" })),
+        ),
+    }
+}
+
+/// Hasil pengiriman per penerima (push / email) untuk notifikasi di riwayat.
+pub async fn notification_deliveries(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let rows = recipients::deliveries(&state.db, id).await.unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+// --- Webhooks (protected) ---
+
+const WEBHOOK_COLUMNS: &str = "id, key_id, url, secret, events, active, created_at";
+const WEBHOOK_DELIVERIES_LIMIT: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct WebhooksQuery {
+    /// Filter per app (`keys.id`).
+    #[serde(default)]
+    pub key_id: Option<i32>,
+}
+
+pub async fn webhooks_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Query(query): Query<WebhooksQuery>,
+) -> impl IntoResponse {
+    let rows: Vec<WebhookRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM webhooks WHERE $1::INTEGER IS NULL OR key_id = $1 ORDER BY id",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(query.key_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+pub async fn webhook_create(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Json(body): Json<CreateWebhookBody>,
+) -> impl IntoResponse {
+    let url = body.url.trim();
+    let valid = match webhooks::validate_events(&body.events) {
+        Ok(()) => webhooks::resolve_public(url).await.map(|_| ()),
+        Err(message) => Err(message),
+    };
+    if let Err(message) = valid {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let key_exists: Option<(i32,)> = sqlx::query_as("SELECT id FROM keys WHERE id = $1")
+        .bind(body.key_id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten();
+    if key_exists.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        );
+    }
+    let row = sqlx::query_as::<_, WebhookRow>(&format!(
+        "INSERT INTO webhooks (key_id, url, secret, events) VALUES ($1, $2, $3, $4) RETURNING {}",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(body.key_id)
+    .bind(url)
+    .bind(webhooks::generate_secret())
+    .bind(&body.events)
+    .fetch_one(&state.db)
+    .await;
+    match row {
+        Ok(r) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "ok": true, "webhook": r })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "insert webhook");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan webhook" })),
+            )
+        }
+    }
+}
+
+pub async fn webhook_update(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateWebhookBody>,
+) -> impl IntoResponse {
+    let existing: Option<WebhookRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM webhooks WHERE id = $1",
+        WEBHOOK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let mut row = match existing {
+        Some(r) => r,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Webhook tidak ditemukan" })),
+            );
+        }
+    };
+    if let Some(url) = body.url.as_deref() {
+        let url = url.trim();
+        if let Err(message) = webhooks::resolve_public(url).await {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.url = url.to_string();
+    }
+    if let Some(events) = body.events {
+        if let Err(message) = webhooks::validate_events(&events) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.events = events;
+    }
+    if let Some(active) = body.active {
+        row.active = active;
+    }
+    let updated = sqlx::query("UPDATE webhooks SET url = $1, events = $2, active = $3 WHERE id = $4")
+        .bind(&row.url)
+        .bind(&row.events)
+        .bind(row.active)
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match updated {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "webhook": row })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "update webhook");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal update webhook" })),
+            )
+        }
+    }
+}
+
+pub async fn webhook_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Webhook tidak ditemukan" })),
+        ),
+    }
+}
+
+/// Log pengiriman terbaru (termasuk percobaan ulang) untuk satu webhook.
+pub async fn webhook_deliveries(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let rows: Vec<DeliveryRow> = sqlx::query_as(
+        "SELECT id, delivery_id, event, attempt, status_code, error, success, created_at FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY id DESC LIMIT $2",
+    )
+    .bind(id)
+    .bind(WEBHOOK_DELIVERIES_LIMIT)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+// --- Channel sinks (protected) ---
+
+const SINK_COLUMNS: &str = "id, key_id, channel, url, format, active, created_at";
+
+#[derive(Deserialize)]
+pub struct SinksQuery {
+    /// Filter per pola channel (persis seperti saat dibuat).
+    #[serde(default)]
+    pub channel: Option<String>,
+    /// Filter per app (`keys.id`).
+    #[serde(default)]
+    pub key_id: Option<i32>,
+}
+
+pub async fn sinks_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Query(query): Query<SinksQuery>,
+) -> impl IntoResponse {
+    let rows: Vec<SinkRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM channel_sinks WHERE ($1::TEXT IS NULL OR channel = $1) AND ($2::INTEGER IS NULL OR key_id = $2) ORDER BY id",
+        SINK_COLUMNS
+    ))
+    .bind(query.channel)
+    .bind(query.key_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+pub async fn sink_create(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Json(body): Json<CreateSinkBody>,
+) -> impl IntoResponse {
+    let channel = body.channel.trim();
+    let url = body.url.trim();
+    if let Err(message) = channel::validate_pattern(channel).and_then(|_| webhooks::validate_url(url)) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let row = sqlx::query_as::<_, SinkRow>(&format!(
+        "INSERT INTO channel_sinks (key_id, channel, url, format) VALUES ($1, $2, $3, $4) RETURNING {}",
+        SINK_COLUMNS
+    ))
+    .bind(body.key_id)
+    .bind(channel)
+    .bind(url)
+    .bind(body.format.as_str())
+    .fetch_one(&state.db)
+    .await;
+    match row {
+        Ok(r) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "ok": true, "sink": r })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "insert channel sink");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan sink" })),
+            )
+        }
+    }
+}
+
+pub async fn sink_update(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateSinkBody>,
+) -> impl IntoResponse {
+    let existing: Option<SinkRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM channel_sinks WHERE id = $1",
+        SINK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let mut row = match existing {
+        Some(r) => r,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Sink tidak ditemukan" })),
+            );
+        }
+    };
+    if let Some(channel) = body.channel.as_deref() {
+        let channel = channel.trim();
+        if let Err(message) = channel::validate_pattern(channel) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.channel = channel.to_string();
+    }
+    if let Some(url) = body.url.as_deref() {
+        let url = url.trim();
+        if let Err(message) = webhooks::validate_url(url) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.url = url.to_string();
+    }
+    if let Some(format) = body.format {
+        row.format = format.as_str().to_string();
+    }
+    if let Some(active) = body.active {
+        row.active = active;
+    }
+    let updated = sqlx::query(
+        "UPDATE channel_sinks SET channel = $1, url = $2, format = $3, active = $4 WHERE id = $5",
+    )
+    .bind(&row.channel)
+    .bind(&row.url)
+    .bind(&row.format)
+    .bind(row.active)
+    .bind(id)
+    .execute(&state.db)
+    .await;
+    match updated {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "sink": row })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "update channel sink");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal update sink" })),
+            )
+        }
+    }
+}
+
+pub async fn sink_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM channel_sinks WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Sink tidak ditemukan" })),
+        ),
+    }
+}
+
+// --- Frequency caps (protected) ---
+
+const FREQUENCY_CAP_COLUMNS: &str = "id, key_id, channel, max_pushes, window_secs, active, created_at";
+
+pub async fn frequency_caps_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Query(query): Query<WebhooksQuery>,
+) -> impl IntoResponse {
+    let rows: Vec<FrequencyCapRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM frequency_caps WHERE $1::INTEGER IS NULL OR key_id = $1 ORDER BY id",
+        FREQUENCY_CAP_COLUMNS
+    ))
+    .bind(query.key_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+pub async fn frequency_cap_create(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Json(body): Json<CreateFrequencyCapBody>,
+) -> impl IntoResponse {
+    let channel = body.channel.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let valid = frequency_caps::validate_limits(body.max_pushes, body.window_secs)
+        .and_then(|_| channel.map_or(Ok(()), channel::validate_pattern));
+    if let Err(message) = valid {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let row = sqlx::query_as::<_, FrequencyCapRow>(&format!(
+        "INSERT INTO frequency_caps (key_id, channel, max_pushes, window_secs) VALUES ($1, $2, $3, $4) RETURNING {}",
+        FREQUENCY_CAP_COLUMNS
+    ))
+    .bind(body.key_id)
+    .bind(channel)
+    .bind(body.max_pushes)
+    .bind(body.window_secs)
+    .fetch_one(&state.db)
+    .await;
+    match row {
+        Ok(r) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({ "ok": true, "frequency_cap": r })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "insert frequency cap");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan frequency cap" })),
+            )
+        }
+    }
+}
+
+pub async fn frequency_cap_update(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateFrequencyCapBody>,
+) -> impl IntoResponse {
+    let existing: Option<FrequencyCapRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM frequency_caps WHERE id = $1",
+        FREQUENCY_CAP_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let mut row = match existing {
+        Some(r) => r,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Frequency cap tidak ditemukan" })),
+            );
+        }
+    };
+    row.max_pushes = body.max_pushes.unwrap_or(row.max_pushes);
+    row.window_secs = body.window_secs.unwrap_or(row.window_secs);
+    row.active = body.active.unwrap_or(row.active);
+    if let Err(message) = frequency_caps::validate_limits(row.max_pushes, row.window_secs) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let updated = sqlx::query(
+        "UPDATE frequency_caps SET max_pushes = $1, window_secs = $2, active = $3 WHERE id = $4",
+    )
+    .bind(row.max_pushes)
+    .bind(row.window_secs)
+    .bind(row.active)
+    .bind(id)
+    .execute(&state.db)
+    .await;
+    match updated {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "frequency_cap": row })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "update frequency cap");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal update frequency cap" })),
+            )
+        }
+    }
+}
+
+pub async fn frequency_cap_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM frequency_caps WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Frequency cap tidak ditemukan" })),
+        ),
+    }
+}
+
+// --- Inbound hooks (protected) ---
+
+const HOOK_COLUMNS: &str = "id, key_id, token, adapter, channel, event, secret, mapping, active, created_at";
+
+pub async fn hooks_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Query(query): Query<WebhooksQuery>,
+) -> impl IntoResponse {
+    let rows: Vec<HookRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM inbound_hooks WHERE $1::INTEGER IS NULL OR key_id = $1 ORDER BY id",
+        HOOK_COLUMNS
+    ))
+    .bind(query.key_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+pub async fn hook_create(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Json(body): Json<CreateHookBody>,
+) -> impl IntoResponse {
+    let event = body
+        .event
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .unwrap_or(inbound_hooks::DEFAULT_EVENT);
+    let valid = inbound_hooks::validate_adapter(&body.adapter)
+        .and_then(|_| inbound_hooks::validate_secret(&body.adapter, body.secret.as_deref()))
+        .and_then(|_| channel::validate_name(&body.channel))
+        .and_then(|_| inbound_hooks::validate_mapping(&body.mapping));
+    if let Err(message) = valid {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": message })),
+        );
+    }
+    let mapping = serde_json::to_string(&body.mapping).unwrap_or_else(|_| "{}".to_string());
+    let row = sqlx::query_as::<_, HookRow>(&format!(
+        "INSERT INTO inbound_hooks (key_id, token, adapter, channel, event, secret, mapping) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+        HOOK_COLUMNS
+    ))
+    .bind(body.key_id)
+    .bind(inbound_hooks::generate_token())
+    .bind(&body.adapter)
+    .bind(&body.channel)
+    .bind(event)
+    .bind(body.secret.filter(|s| !s.is_empty()))
+    .bind(mapping)
+    .fetch_one(&state.db)
+    .await;
+    match row {
+        Ok(r) => (
+            StatusCode::CREATED,
+            Json(serde_json::json!({
+                "ok": true,
+                "hook": r,
+                "url": format!("/hooks/{}/{}", r.key_id, r.adapter)
+            })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "ok": false, "message": "Hook untuk app dan adapter ini sudah ada" })),
+        ),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Key tidak ditemukan" })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "insert inbound hook");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal menyimpan hook" })),
+            )
+        }
+    }
+}
+
+pub async fn hook_update(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+    Json(body): Json<UpdateHookBody>,
+) -> impl IntoResponse {
+    let existing: Option<HookRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM inbound_hooks WHERE id = $1",
+        HOOK_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let mut row = match existing {
+        Some(r) => r,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "ok": false, "message": "Hook tidak ditemukan" })),
+            );
+        }
+    };
+    if let Some(ch) = body.channel {
+        if let Err(message) = channel::validate_name(&ch) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.channel = ch;
+    }
+    if let Some(e) = body.event.as_deref() {
+        if !e.trim().is_empty() {
+            row.event = e.trim().to_string();
+        }
+    }
+    if let Some(secret) = body.secret {
+        if let Err(message) = inbound_hooks::validate_secret(&row.adapter, Some(&secret)) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.secret = Some(secret).filter(|s| !s.is_empty());
+    }
+    if let Some(mapping) = body.mapping {
+        if let Err(message) = inbound_hooks::validate_mapping(&mapping) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "ok": false, "message": message })),
+            );
+        }
+        row.mapping = serde_json::to_string(&mapping).unwrap_or_else(|_| "{}".to_string());
+    }
+    if let Some(active) = body.active {
+        row.active = active;
+    }
+    let updated = sqlx::query(
+        "UPDATE inbound_hooks SET channel = $1, event = $2, secret = $3, mapping = $4, active = $5 WHERE id = $6",
+    )
+    .bind(&row.channel)
+    .bind(&row.event)
+    .bind(&row.secret)
+    .bind(&row.mapping)
+    .bind(row.active)
+    .bind(id)
+    .execute(&state.db)
+    .await;
+    match updated {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true, "hook": row })),
+        ),
+        Err(e) => {
+            tracing::error!(%e, "update inbound hook");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "ok": false, "message": "Gagal update hook" })),
+            )
+        }
+    }
+}
+
+pub async fn hook_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(id): Path<i32>,
+) -> impl IntoResponse {
+    let result = sqlx::query("DELETE FROM inbound_hooks WHERE id = $1")
+        .bind(id)
+        .execute(&state.db)
+        .await;
+    match result {
+        Ok(r) if r.rows_affected() > 0 => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "ok": true })),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Hook tidak ditemukan" })),
+        ),
+    }
+}
+
+// --- Channels (protected) ---
+
+const CHANNEL_PAGE_SIZE: usize = 50;
+const CHANNEL_PAGE_SIZE_MAX: usize = 200;
+
+#[derive(Deserialize)]
+pub struct ChannelSubscribersQuery {
+    /// Halaman (mulai 1).
+    #[serde(default)]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub per_page: Option<usize>,
+}
+
+pub async fn channels_list(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+) -> impl IntoResponse {
+    let counts = {
+        let subs = state.subscriptions.read().await;
+        subs.channel_counts(None)
+    };
+    let last = state.channel_last_triggered.read().await;
+    let mut names: Vec<&String> = counts.keys().chain(last.keys()).collect();
+    names.sort();
+    names.dedup();
+    let rows: Vec<serde_json::Value> = names
+        .into_iter()
+        .map(|name| {
+            serde_json::json!({
+                "name": name,
+                "subscribers": counts.get(name).copied().unwrap_or(0),
+                "last_triggered_at": last.get(name),
+            })
+        })
+        .collect();
+    (StatusCode::OK, Json(serde_json::json!(rows)))
+}
+
+pub async fn channel_show(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(name): Path<String>,
+    Query(query): Query<ChannelSubscribersQuery>,
+) -> impl IntoResponse {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(CHANNEL_PAGE_SIZE)
+        .clamp(1, CHANNEL_PAGE_SIZE_MAX);
+    let (total, subscribers) = {
+        let subs = state.subscriptions.read().await;
+        let matched = subs.in_channel(&name);
+        let page_rows: Vec<serde_json::Value> = matched
+            .iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .map(|s| serde_json::json!({ "id": s.id, "endpoint": s.endpoint, "channels": s.channels }))
+            .collect();
+        (matched.len(), page_rows)
+    };
+    let last_triggered_at = state.channel_last_triggered.read().await.get(&name).copied();
+    if total == 0 && last_triggered_at.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Channel tidak ditemukan" })),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "ok": true,
+            "name": name,
+            "total": total,
+            "page": page,
+            "per_page": per_page,
+            "last_triggered_at": last_triggered_at,
+            "subscribers": subscribers
+        })),
+    )
+}
+
+pub async fn channel_delete(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let removed = {
+        let mut subs = state.subscriptions.write().await;
+        let removed = subs.remove_channel(&name);
+        if removed > 0 {
+            let to_save = subs.clone();
+            if let Err(e) = save_subscriptions(&to_save).await {
+                warn!(error = %e, "failed to persist subscriptions");
+            }
+        }
+        removed
+    };
+    let had_activity = state
+        .channel_last_triggered
+        .write()
+        .await
+        .remove(&name)
+        .is_some();
+    if removed == 0 && !had_activity {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "ok": false, "message": "Channel tidak ditemukan" })),
+        );
+    }
+    info!(channel = %name, removed, "channel deleted");
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "ok": true, "removed": removed })),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct ChannelMembersQuery {
+    /// App (`keys.id`); presence channel dengan nama sama di app lain terpisah.
+    pub key_id: i32,
+}
+
+pub async fn channel_members(
+    State(state): State<AppState>,
+    AuthUser(_): AuthUser,
+    Path(name): Path<String>,
+    Query(query): Query<ChannelMembersQuery>,
+) -> impl IntoResponse {
+    if !name.starts_with(presence::PRESENCE_PREFIX) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "ok": false, "message": "Bukan presence channel" })),
+        );
+    }
+    let members = state.presence.members(query.key_id, &name);
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "ok": true,
+            "name": name,
+            "key_id": query.key_id,
+            "count": members.len(),
+            "members": members
+        })),
+    )
+}
@@ -0,0 +1,89 @@
+//! Riwayat notifikasi di tabel `notification_history` (dipakai `/notify` dan API Gotify).
+
+use sqlx::{FromRow, PgPool};
+
+#[derive(Debug, FromRow)]
+pub struct HistoryRow {
+    pub id: i64,
+    pub key_id: Option<i32>,
+    pub title: String,
+    pub message: String,
+    pub priority: i32,
+    /// JSON, disimpan sebagai TEXT.
+    pub extras: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Simpan satu notifikasi. Return ID riwayat.
+pub async fn record(
+    pool: &PgPool,
+    key_id: Option<i32>,
+    title: &str,
+    message: &str,
+    priority: i32,
+    extras: Option<&serde_json::Value>,
+) -> anyhow::Result<i64> {
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO notification_history (key_id, title, message, priority, extras) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(key_id)
+    .bind(title)
+    .bind(message)
+    .bind(priority)
+    .bind(extras.map(|e| e.to_string()))
+    .fetch_one(pool)
+    .await?;
+    Ok(id)
+}
+
+/// Ambil ID riwayat berikutnya tanpa menyimpan, untuk pesan yang baru dicatat setelah
+/// terkirim (lihat `record_as`).
+pub async fn next_id(pool: &PgPool) -> anyhow::Result<i64> {
+    let (id,): (i64,) =
+        sqlx::query_as("SELECT nextval(pg_get_serial_sequence('notification_history', 'id'))")
+            .fetch_one(pool)
+            .await?;
+    Ok(id)
+}
+
+/// Simpan satu notifikasi dengan ID dari `next_id`.
+pub async fn record_as(
+    pool: &PgPool,
+    id: i64,
+    key_id: Option<i32>,
+    title: &str,
+    message: &str,
+    priority: i32,
+    extras: Option<&serde_json::Value>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO notification_history (id, key_id, title, message, priority, extras) VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(id)
+    .bind(key_id)
+    .bind(title)
+    .bind(message)
+    .bind(priority)
+    .bind(extras.map(|e| e.to_string()))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Riwayat terbaru lebih dulu. `before` = hanya ID lebih kecil (paging).
+pub async fn list(
+    pool: &PgPool,
+    key_id: Option<i32>,
+    before: Option<i64>,
+    limit: i64,
+) -> anyhow::Result<Vec<HistoryRow>> {
+    let rows = sqlx::query_as(
+        "SELECT id, key_id, title, message, priority, extras, created_at FROM notification_history WHERE ($1::INTEGER IS NULL OR key_id = $1) AND ($2::BIGINT IS NULL OR id < $2) ORDER BY id DESC LIMIT $3",
+    )
+    .bind(key_id)
+    .bind(before)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
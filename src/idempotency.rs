@@ -0,0 +1,182 @@
+//! Header `Idempotency-Key` untuk endpoint yang mengirim push (`/trigger`, `/notify`).
+//! Request ulang dengan key yang sama dalam jendela `IDEMPOTENCY_TTL` mendapat respons
+//! asli tanpa mengirim ulang. Disimpan di tabel `idempotency_keys`.
+//! Reservasi yang belum selesai setelah `IN_PROGRESS_LEASE` (handler di-drop / panic) boleh
+//! diambil alih request berikutnya.
+//!
+//! Key dicatat per pemanggil (app terautentikasi atau publik), jadi pemanggil lain tidak bisa
+//! mengambil respons lewat key yang sama. Key yang dipakai ulang dengan body berbeda ditolak
+//! `422` (dicocokkan lewat `fingerprint` body).
+
+use axum::{
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::future::Future;
+use tracing::warn;
+
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENCY_TTL: Duration = Duration::hours(24);
+/// Lama reservasi tanpa hasil dianggap masih diproses.
+const IN_PROGRESS_LEASE: Duration = Duration::minutes(5);
+const MAX_KEY_LEN: usize = 255;
+
+enum Reservation {
+    /// Key baru, request ini yang memproses.
+    New,
+    /// Sudah ada hasil sebelumnya.
+    Replay(StatusCode, serde_json::Value),
+    /// Request lain dengan key sama masih diproses.
+    InProgress,
+    /// Key sudah dipakai untuk body lain.
+    Mismatch,
+}
+
+/// Pemanggil pemilik key: app terautentikasi atau publik (tanpa auth).
+fn owner(app_id: Option<i32>) -> String {
+    match app_id {
+        Some(id) => format!("app:{}", id),
+        None => "public".to_string(),
+    }
+}
+
+/// Hash body request (JSON ter-parse, jadi spasi tidak berpengaruh) untuk mendeteksi key yang
+/// dipakai ulang dengan body berbeda.
+pub fn fingerprint(request: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(request.to_string().as_bytes()))
+}
+
+async fn reserve(
+    pool: &PgPool,
+    scope: &str,
+    owner: &str,
+    key: &str,
+    request_hash: &str,
+) -> anyhow::Result<Reservation> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+    // Baris tanpa hasil yang lebih tua dari lease diambil alih (request sebelumnya tidak selesai).
+    let inserted = sqlx::query(
+        "INSERT INTO idempotency_keys (scope, owner, key, request_hash, expires_at) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (scope, owner, key) DO UPDATE SET request_hash = EXCLUDED.request_hash, created_at = NOW(), expires_at = EXCLUDED.expires_at WHERE idempotency_keys.status_code IS NULL AND idempotency_keys.created_at < $6",
+    )
+    .bind(scope)
+    .bind(owner)
+    .bind(key)
+    .bind(request_hash)
+    .bind(Utc::now() + IDEMPOTENCY_TTL)
+    .bind(Utc::now() - IN_PROGRESS_LEASE)
+    .execute(pool)
+    .await?;
+    if inserted.rows_affected() > 0 {
+        return Ok(Reservation::New);
+    }
+    let row: Option<(String, Option<i32>, Option<String>)> = sqlx::query_as(
+        "SELECT request_hash, status_code, response FROM idempotency_keys WHERE scope = $1 AND owner = $2 AND key = $3",
+    )
+    .bind(scope)
+    .bind(owner)
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+    match row {
+        Some((hash, ..)) if hash != request_hash => Ok(Reservation::Mismatch),
+        Some((_, Some(status), Some(response))) => {
+            let status = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+            Ok(Reservation::Replay(status, serde_json::from_str(&response)?))
+        }
+        Some(_) => Ok(Reservation::InProgress),
+        // Baris terhapus di antara INSERT dan SELECT (kedaluwarsa); anggap sedang diproses.
+        None => Ok(Reservation::InProgress),
+    }
+}
+
+async fn complete(
+    pool: &PgPool,
+    scope: &str,
+    owner: &str,
+    key: &str,
+    status: StatusCode,
+    response: &serde_json::Value,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "UPDATE idempotency_keys SET status_code = $1, response = $2 WHERE scope = $3 AND owner = $4 AND key = $5",
+    )
+    .bind(status.as_u16() as i32)
+    .bind(response.to_string())
+    .bind(scope)
+    .bind(owner)
+    .bind(key)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Jalankan `handler` sekali per `Idempotency-Key` (per `scope` dan pemanggil `app_id`).
+/// `request_hash` dari `fingerprint`. Tanpa header, `handler` selalu dijalankan.
+pub async fn run<F, Fut>(
+    pool: &PgPool,
+    scope: &str,
+    app_id: Option<i32>,
+    request_hash: &str,
+    headers: &HeaderMap,
+    handler: F,
+) -> (StatusCode, Json<serde_json::Value>)
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = (StatusCode, Json<serde_json::Value>)>,
+{
+    let key = match headers
+        .get(IDEMPOTENCY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+    {
+        None => return handler().await,
+        Some(k) if k.is_empty() || k.len() > MAX_KEY_LEN => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "ok": false,
+                    "message": format!("{} harus 1-{} karakter", IDEMPOTENCY_HEADER, MAX_KEY_LEN)
+                })),
+            );
+        }
+        Some(k) => k.to_string(),
+    };
+    let owner = owner(app_id);
+    match reserve(pool, scope, &owner, &key, request_hash).await {
+        Ok(Reservation::New) => {}
+        Ok(Reservation::Replay(status, response)) => return (status, Json(response)),
+        Ok(Reservation::Mismatch) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(serde_json::json!({
+                    "ok": false,
+                    "message": format!("{} ini sudah dipakai untuk request dengan body berbeda", IDEMPOTENCY_HEADER)
+                })),
+            );
+        }
+        Ok(Reservation::InProgress) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "ok": false,
+                    "message": "Request dengan Idempotency-Key ini masih diproses"
+                })),
+            );
+        }
+        Err(e) => {
+            // DB bermasalah: tetap proses daripada menolak pengiriman.
+            warn!(error = %e, "idempotency reserve failed");
+            return handler().await;
+        }
+    }
+    let (status, Json(response)) = handler().await;
+    if let Err(e) = complete(pool, scope, &owner, &key, status, &response).await {
+        warn!(error = %e, "idempotency complete failed");
+    }
+    (status, Json(response))
+}
@@ -0,0 +1,433 @@
+//! Webhook masuk: GitHub, Grafana alerting, atau JSON bebas diarahkan langsung ke
+//! `POST /hooks/:app/:adapter` (`app` = `keys.id`). Payload dipetakan ke title/body/url lalu
+//! dikirim lewat pipeline trigger ke channel app tersebut yang dikonfigurasi di tabel
+//! `inbound_hooks`.
+//!
+//! - Mapping per hook: JSON pointer (RFC 6901) untuk `title`/`body`/`url`, menimpa hasil
+//!   adapter. Untuk adapter `generic` defaultnya `/title`, `/body`, `/url`.
+//! - Signature HMAC-SHA256 atas body mentah: GitHub `X-Hub-Signature-256: sha256=<hex>`,
+//!   Grafana `X-Grafana-Alerting-Signature: <hex>`, generic `X-Signature-256: sha256=<hex>`.
+//!   Adapter yang mendukung signature (github, grafana) wajib punya secret; generic opsional.
+//! - Hook generic tanpa secret diautentikasi token acak per hook (`inbound_hooks.token`) di
+//!   header `X-Hook-Token` atau query `?token=`.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::FromRow;
+use tracing::info;
+
+use crate::handlers::{publish_trigger, TriggerBody};
+use crate::signature;
+use crate::state::AppState;
+
+pub const ADAPTERS: [&str; 3] = ["github", "grafana", "generic"];
+/// Adapter yang pengirimnya bisa menandatangani payload, jadi secret wajib.
+const SIGNED_ADAPTERS: [&str; 2] = ["github", "grafana"];
+pub const DEFAULT_EVENT: &str = "notification";
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct HookRow {
+    pub id: i32,
+    pub key_id: i32,
+    /// Auth hook tanpa secret (`X-Hook-Token` / `?token=`).
+    pub token: String,
+    pub adapter: String,
+    pub channel: String,
+    pub event: String,
+    pub secret: Option<String>,
+    /// JSON `HookMapping`, disimpan sebagai TEXT.
+    #[serde(serialize_with = "serialize_mapping")]
+    pub mapping: String,
+    pub active: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn serialize_mapping<S: Serializer>(mapping: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    parse_mapping(mapping).serialize(serializer)
+}
+
+/// JSON pointer ke field payload masuk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HookMapping {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+pub fn parse_mapping(mapping: &str) -> HookMapping {
+    serde_json::from_str(mapping).unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateHookBody {
+    pub key_id: i32,
+    pub adapter: String,
+    pub channel: String,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub mapping: HookMapping,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateHookBody {
+    pub channel: Option<String>,
+    pub event: Option<String>,
+    /// String kosong = hapus secret (hook lalu diautentikasi token); hanya untuk adapter generic.
+    pub secret: Option<String>,
+    pub mapping: Option<HookMapping>,
+    pub active: Option<bool>,
+}
+
+pub fn validate_adapter(adapter: &str) -> Result<(), String> {
+    if ADAPTERS.contains(&adapter) {
+        Ok(())
+    } else {
+        Err(format!(
+            "adapter tidak dikenal: {} (pilihan: {})",
+            adapter,
+            ADAPTERS.join(", ")
+        ))
+    }
+}
+
+pub fn requires_secret(adapter: &str) -> bool {
+    SIGNED_ADAPTERS.contains(&adapter)
+}
+
+/// Secret wajib untuk adapter github/grafana.
+pub fn validate_secret(adapter: &str, secret: Option<&str>) -> Result<(), String> {
+    if requires_secret(adapter) && secret.is_none_or(str::is_empty) {
+        Err(format!("adapter {} wajib memakai secret", adapter))
+    } else {
+        Ok(())
+    }
+}
+
+/// Token hook baru.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+pub fn validate_mapping(mapping: &HookMapping) -> Result<(), String> {
+    [&mapping.title, &mapping.body, &mapping.url]
+        .into_iter()
+        .flatten()
+        .try_for_each(|p| {
+            if p.is_empty() || p.starts_with('/') {
+                Ok(())
+            } else {
+                Err(format!("mapping harus JSON pointer (diawali /): {}", p))
+            }
+        })
+}
+
+/// Hasil pemetaan payload masuk.
+struct Notification {
+    title: String,
+    body: String,
+    url: Option<String>,
+}
+
+fn text(value: &serde_json::Value, pointer: &str) -> Option<String> {
+    match value.pointer(pointer)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Baris pertama saja (pesan commit, dsb).
+fn first_line(s: &str) -> String {
+    s.lines().next().unwrap_or("").to_string()
+}
+
+/// Return None untuk event yang tidak perlu diteruskan (mis. `ping`).
+fn github(event: &str, p: &serde_json::Value) -> Option<Notification> {
+    let repo = text(p, "/repository/full_name").unwrap_or_else(|| "GitHub".to_string());
+    let action = text(p, "/action").unwrap_or_default();
+    let n = match event {
+        "ping" => return None,
+        "push" => {
+            let branch = text(p, "/ref")
+                .map(|r| r.trim_start_matches("refs/heads/").to_string())
+                .unwrap_or_default();
+            let commits = p["commits"].as_array().map_or(0, Vec::len);
+            Notification {
+                title: format!("[{}] {} commit ke {}", repo, commits, branch),
+                body: text(p, "/head_commit/message")
+                    .map(|m| first_line(&m))
+                    .unwrap_or_default(),
+                url: text(p, "/compare"),
+            }
+        }
+        "pull_request" => Notification {
+            title: format!(
+                "[{}] PR #{} {}",
+                repo,
+                text(p, "/pull_request/number").unwrap_or_default(),
+                action
+            ),
+            body: text(p, "/pull_request/title").unwrap_or_default(),
+            url: text(p, "/pull_request/html_url"),
+        },
+        "issues" => Notification {
+            title: format!(
+                "[{}] Issue #{} {}",
+                repo,
+                text(p, "/issue/number").unwrap_or_default(),
+                action
+            ),
+            body: text(p, "/issue/title").unwrap_or_default(),
+            url: text(p, "/issue/html_url"),
+        },
+        "issue_comment" => Notification {
+            title: format!(
+                "[{}] Komentar di #{}",
+                repo,
+                text(p, "/issue/number").unwrap_or_default()
+            ),
+            body: text(p, "/comment/body")
+                .map(|m| first_line(&m))
+                .unwrap_or_default(),
+            url: text(p, "/comment/html_url"),
+        },
+        "release" => Notification {
+            title: format!("[{}] Release {}", repo, action),
+            body: text(p, "/release/name")
+                .filter(|n| !n.is_empty())
+                .or_else(|| text(p, "/release/tag_name"))
+                .unwrap_or_default(),
+            url: text(p, "/release/html_url"),
+        },
+        "workflow_run" => Notification {
+            title: format!(
+                "[{}] Workflow {}",
+                repo,
+                text(p, "/workflow_run/conclusion")
+                    .or_else(|| text(p, "/workflow_run/status"))
+                    .unwrap_or_default()
+            ),
+            body: text(p, "/workflow_run/name").unwrap_or_default(),
+            url: text(p, "/workflow_run/html_url"),
+        },
+        other => Notification {
+            title: format!("[{}] {} {}", repo, other, action)
+                .trim_end()
+                .to_string(),
+            body: text(p, "/sender/login")
+                .map(|l| format!("oleh {}", l))
+                .unwrap_or_default(),
+            url: text(p, "/repository/html_url"),
+        },
+    };
+    Some(n)
+}
+
+/// Payload webhook Grafana unified alerting.
+fn grafana(p: &serde_json::Value) -> Notification {
+    let status = text(p, "/status").unwrap_or_default().to_uppercase();
+    let title = text(p, "/title").unwrap_or_else(|| {
+        format!(
+            "[{}] {}",
+            status,
+            text(p, "/commonLabels/alertname").unwrap_or_else(|| "Alert".to_string())
+        )
+    });
+    let body = text(p, "/message")
+        .or_else(|| text(p, "/commonAnnotations/summary"))
+        .or_else(|| text(p, "/alerts/0/annotations/summary"))
+        .unwrap_or_default();
+    let url = text(p, "/alerts/0/generatorURL").or_else(|| text(p, "/externalURL"));
+    Notification { title, body, url }
+}
+
+fn generic(p: &serde_json::Value) -> Notification {
+    Notification {
+        title: text(p, "/title").unwrap_or_default(),
+        body: text(p, "/body")
+            .or_else(|| text(p, "/message"))
+            .unwrap_or_default(),
+        url: text(p, "/url"),
+    }
+}
+
+fn apply_mapping(
+    mut n: Notification,
+    mapping: &HookMapping,
+    p: &serde_json::Value,
+) -> Notification {
+    if let Some(v) = mapping.title.as_deref().and_then(|ptr| text(p, ptr)) {
+        n.title = v;
+    }
+    if let Some(v) = mapping.body.as_deref().and_then(|ptr| text(p, ptr)) {
+        n.body = v;
+    }
+    if let Some(v) = mapping.url.as_deref().and_then(|ptr| text(p, ptr)) {
+        n.url = Some(v);
+    }
+    n
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn verify_signature(adapter: &str, secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    let signature = match adapter {
+        "github" => header(headers, "X-Hub-Signature-256").and_then(|s| s.strip_prefix("sha256=")),
+        "grafana" => header(headers, "X-Grafana-Alerting-Signature"),
+        _ => header(headers, "X-Signature-256").and_then(|s| s.strip_prefix("sha256=")),
+    };
+    let Some(signature) = signature else {
+        return false;
+    };
+    // HMAC atas byte mentah; body non-UTF-8 tidak mungkin JSON valid.
+    let Ok(body) = std::str::from_utf8(body) else {
+        return false;
+    };
+    signature::verify(secret, body, signature)
+}
+
+fn reply(status: StatusCode, value: serde_json::Value) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(value))
+}
+
+fn token_matches(expected: &str, token: Option<&str>) -> bool {
+    token.is_some_and(|t| {
+        t.len() == expected.len() && openssl::memcmp::eq(t.as_bytes(), expected.as_bytes())
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiveQuery {
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// `POST /hooks/:app/:adapter`
+pub async fn receive(
+    State(state): State<AppState>,
+    Path((app_id, adapter)): Path<(i32, String)>,
+    Query(query): Query<ReceiveQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let hook: Option<HookRow> = sqlx::query_as(
+        "SELECT id, key_id, token, adapter, channel, event, secret, mapping, active, created_at FROM inbound_hooks WHERE key_id = $1 AND adapter = $2 AND active",
+    )
+    .bind(app_id)
+    .bind(&adapter)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    let Some(hook) = hook else {
+        return reply(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({ "ok": false, "message": "Hook tidak ditemukan" }),
+        );
+    };
+    let secret = hook.secret.as_deref().filter(|s| !s.is_empty());
+    if secret.is_none() && requires_secret(&hook.adapter) {
+        // Hook lama tanpa secret: tolak sampai secret diset.
+        return reply(
+            StatusCode::UNAUTHORIZED,
+            serde_json::json!({ "ok": false, "message": "Hook belum punya secret" }),
+        );
+    }
+    match secret {
+        Some(secret) => {
+            if !verify_signature(&hook.adapter, secret, &headers, &body) {
+                return reply(
+                    StatusCode::UNAUTHORIZED,
+                    serde_json::json!({ "ok": false, "message": "Signature tidak valid" }),
+                );
+            }
+        }
+        None => {
+            let token = header(&headers, "X-Hook-Token").or(query.token.as_deref());
+            if !token_matches(&hook.token, token) {
+                return reply(
+                    StatusCode::UNAUTHORIZED,
+                    serde_json::json!({ "ok": false, "message": "Token hook tidak valid" }),
+                );
+            }
+        }
+    }
+    let payload: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(p) => p,
+        Err(e) => {
+            return reply(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({ "ok": false, "message": e.to_string() }),
+            );
+        }
+    };
+    let notification = match hook.adapter.as_str() {
+        "github" => {
+            let event = header(&headers, "X-GitHub-Event").unwrap_or("");
+            match github(event, &payload) {
+                Some(n) => n,
+                None => {
+                    return reply(
+                        StatusCode::OK,
+                        serde_json::json!({ "ok": true, "message": "Event diabaikan" }),
+                    );
+                }
+            }
+        }
+        "grafana" => grafana(&payload),
+        _ => generic(&payload),
+    };
+    let notification = apply_mapping(notification, &parse_mapping(&hook.mapping), &payload);
+    if notification.title.is_empty() && notification.body.is_empty() {
+        return reply(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            serde_json::json!({ "ok": false, "message": "title/body tidak ditemukan di payload" }),
+        );
+    }
+
+    let trigger = TriggerBody {
+        channels: vec![hook.channel.clone()],
+        channels_all: Vec::new(),
+        exclude_channels: Vec::new(),
+        exclude: Vec::new(),
+        event: hook.event.clone(),
+        data: serde_json::json!({
+            "title": notification.title,
+            "body": notification.body,
+            "url": notification.url,
+            "source": hook.adapter
+        }),
+        fetch_on_push: None,
+        urgency: None,
+        app_id: Some(hook.key_id),
+    };
+    match publish_trigger(&state, &trigger).await {
+        Ok(outcome) => {
+            info!(hook_id = hook.id, adapter = %hook.adapter, channel = %hook.channel, sent = outcome.sent, "inbound hook forwarded");
+            reply(
+                StatusCode::OK,
+                serde_json::json!({ "ok": true, "sent": outcome.sent, "failed": outcome.failed }),
+            )
+        }
+        Err(e) => reply(
+            e.status,
+            serde_json::json!({ "ok": false, "message": e.message }),
+        ),
+    }
+}
@@ -9,8 +9,9 @@ use sqlx::FromRow;
 pub struct KeyRow {
     pub id: i32,
     pub name: String,
-    pub key: String,
     pub public_key: String,
+    /// Secret HMAC app (REST API, auth channel, token user).
+    pub secret: String,
     pub domain: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
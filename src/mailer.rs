@@ -0,0 +1,245 @@
+//! Kirim email lewat SMTP relay, dipakai sebagai fallback untuk user tanpa push (lihat
+//! `handlers::send_notify`).
+//!
+//! Konfigurasi lewat env (aktif jika `EMAIL_SMTP_HOST` di-set):
+//! - `EMAIL_SMTP_HOST`, `EMAIL_SMTP_PORT` (587), `EMAIL_SMTP_USERNAME` / `EMAIL_SMTP_PASSWORD`
+//!   (opsional, AUTH PLAIN), `EMAIL_FROM` (wajib).
+//! - `EMAIL_SMTP_TLS`: `starttls` (default), `tls` (implicit, default jika port 465), atau `none`
+//!   (relay lokal tepercaya saja).
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+use rand_core::{OsRng, RngCore};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 587;
+const IMPLICIT_TLS_PORT: u16 = 465;
+const TIMEOUT: Duration = Duration::from_secs(30);
+const BASE64_LINE_LEN: usize = 76;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Security {
+    StartTls,
+    Implicit,
+    Plain,
+}
+
+#[derive(Clone)]
+struct Config {
+    host: String,
+    port: u16,
+    tls: Security,
+    credentials: Option<(String, String)>,
+    from: String,
+}
+
+/// SMTP relay; `None` = fallback email tidak dikonfigurasi.
+#[derive(Clone, Default)]
+pub struct Mailer {
+    config: Option<Config>,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+impl Mailer {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let Some(host) = env("EMAIL_SMTP_HOST") else {
+            return Ok(Self::default());
+        };
+        let port = match env("EMAIL_SMTP_PORT") {
+            Some(p) => p
+                .parse()
+                .map_err(|_| anyhow::anyhow!("EMAIL_SMTP_PORT tidak valid: {}", p))?,
+            None => DEFAULT_PORT,
+        };
+        let tls = match env("EMAIL_SMTP_TLS").as_deref() {
+            Some("starttls") => Security::StartTls,
+            Some("tls") => Security::Implicit,
+            Some("none") => Security::Plain,
+            Some(other) => anyhow::bail!("EMAIL_SMTP_TLS tidak dikenal: {}", other),
+            None if port == IMPLICIT_TLS_PORT => Security::Implicit,
+            None => Security::StartTls,
+        };
+        let from = env("EMAIL_FROM")
+            .ok_or_else(|| anyhow::anyhow!("EMAIL_FROM wajib diisi jika EMAIL_SMTP_HOST di-set"))?;
+        Ok(Self {
+            config: Some(Config {
+                host,
+                port,
+                tls,
+                credentials: env("EMAIL_SMTP_USERNAME")
+                    .map(|u| (u, env("EMAIL_SMTP_PASSWORD").unwrap_or_default())),
+                from,
+            }),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Kirim email teks biasa ke satu penerima.
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> anyhow::Result<()> {
+        let Some(config) = self.config.clone() else {
+            anyhow::bail!("SMTP relay belum dikonfigurasi");
+        };
+        let to = to.to_string();
+        let message = build_message(&config.from, &to, subject, body);
+        tokio::task::spawn_blocking(move || deliver(&config, &to, &message)).await?
+    }
+}
+
+/// Header dengan karakter non-ASCII (atau baris baru) di-encode (RFC 2047).
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() && !value.contains(['\r', '\n']) {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn build_message(from: &str, to: &str, subject: &str, body: &str) -> String {
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    let domain = from.rsplit_once('@').map_or("localhost", |(_, d)| d);
+    let encoded = STANDARD.encode(body);
+    // Base64 per baris 76 karakter; tidak ada baris diawali `.`, jadi tanpa dot-stuffing.
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(BASE64_LINE_LEN)
+        .map(|c| std::str::from_utf8(c).unwrap_or_default())
+        .collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        from,
+        to,
+        encode_header(subject),
+        chrono::Utc::now().to_rfc2822(),
+        hex::encode(id),
+        domain,
+        lines.join("\r\n")
+    )
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+fn connect_tls(host: &str, tcp: TcpStream) -> anyhow::Result<Stream> {
+    let connector = SslConnector::builder(SslMethod::tls_client())?.build();
+    let stream = connector
+        .connect(host, tcp)
+        .map_err(|e| anyhow::anyhow!("TLS handshake: {}", e))?;
+    Ok(Stream::Tls(Box::new(stream)))
+}
+
+/// Baca balasan SMTP (multi-baris `250-...` sampai `250 ...`). Return (kode, teks).
+fn read_reply(stream: &mut Stream) -> anyhow::Result<(u16, String)> {
+    let mut text = String::new();
+    loop {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        while !line.ends_with(b"\r\n") {
+            if stream.read(&mut byte)? == 0 {
+                anyhow::bail!("koneksi SMTP ditutup");
+            }
+            line.push(byte[0]);
+        }
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        let code = line
+            .get(..3)
+            .and_then(|c| c.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("balasan SMTP tidak valid: {}", line))?;
+        text.push_str(line.get(4..).unwrap_or_default());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok((code, text));
+        }
+        text.push('\n');
+    }
+}
+
+/// Kirim perintah dan pastikan kode balasan sesuai.
+fn command(stream: &mut Stream, line: &str, expect: u16) -> anyhow::Result<String> {
+    stream.write_all(line.as_bytes())?;
+    stream.write_all(b"\r\n")?;
+    expect_reply(stream, expect)
+}
+
+fn expect_reply(stream: &mut Stream, expect: u16) -> anyhow::Result<String> {
+    let (code, text) = read_reply(stream)?;
+    if code != expect {
+        anyhow::bail!("SMTP {}: {}", code, text);
+    }
+    Ok(text)
+}
+
+fn deliver(config: &Config, to: &str, message: &str) -> anyhow::Result<()> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let mut stream = match config.tls {
+        Security::Implicit => connect_tls(&config.host, tcp)?,
+        _ => Stream::Plain(tcp),
+    };
+    expect_reply(&mut stream, 220)?;
+    let ehlo = "EHLO push-notif";
+    let mut capabilities = command(&mut stream, ehlo, 250)?;
+    if config.tls == Security::StartTls {
+        command(&mut stream, "STARTTLS", 220)?;
+        let Stream::Plain(tcp) = stream else {
+            unreachable!("STARTTLS hanya dari koneksi plain");
+        };
+        stream = connect_tls(&config.host, tcp)?;
+        capabilities = command(&mut stream, ehlo, 250)?;
+    }
+    if let Some((username, password)) = &config.credentials {
+        if !capabilities.to_ascii_uppercase().contains("AUTH") {
+            anyhow::bail!("SMTP relay tidak mendukung AUTH");
+        }
+        let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+        command(&mut stream, &format!("AUTH PLAIN {}", token), 235)?;
+    }
+    command(&mut stream, &format!("MAIL FROM:<{}>", config.from), 250)?;
+    // 251 = bukan user lokal, tetap diteruskan relay.
+    stream.write_all(format!("RCPT TO:<{}>\r\n", to).as_bytes())?;
+    match read_reply(&mut stream)? {
+        (250 | 251, _) => {}
+        (code, text) => anyhow::bail!("SMTP {}: {}", code, text),
+    }
+    command(&mut stream, "DATA", 354)?;
+    stream.write_all(message.as_bytes())?;
+    command(&mut stream, ".", 250)?;
+    let _ = command(&mut stream, "QUIT", 221);
+    Ok(())
+}
@@ -1,13 +1,40 @@
+mod apns;
 mod auth;
+mod channel;
 mod db;
+mod fcm;
+mod frequency_caps;
+mod gotify;
 mod handlers;
+mod history;
+mod idempotency;
+mod inbound_hooks;
 mod keys;
+mod mailer;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod ntfy;
+mod payload;
+mod payload_signer;
+mod presence;
 mod push_service;
+mod pusher_api;
+mod quiet_hours;
+mod realtime;
+mod recipients;
+mod signature;
+mod sinks;
+#[cfg(feature = "smtp")]
+mod smtp;
 mod state;
+mod transport;
+mod webhook_transport;
+mod webhooks;
+mod websocket;
 
 use axum::{
     http::StatusCode,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use tower_http::cors::{Any, CorsLayer};
@@ -21,17 +48,99 @@ async fn main() -> anyhow::Result<()> {
     init_logging()?;
 
     let state = AppState::new().await?;
+    quiet_hours::spawn_scheduler(state.clone());
+    frequency_caps::spawn_cleanup(state.clone());
+    #[cfg(feature = "mqtt")]
+    mqtt::spawn(state.clone())?;
+    #[cfg(feature = "smtp")]
+    smtp::spawn(state.clone()).await?;
     let api_protected = Router::new()
         .route("/me", get(handlers::me))
         .route("/keys", get(handlers::keys_list).post(handlers::key_create))
         .route("/keys/:id", put(handlers::key_update).delete(handlers::key_delete))
-        .route("/keys/:id/regenerate", post(handlers::key_regenerate));
+        .route("/keys/:id/regenerate", post(handlers::key_regenerate))
+        .route(
+            "/keys/:id/fcm",
+            put(handlers::key_fcm_put).delete(handlers::key_fcm_delete),
+        )
+        .route(
+            "/keys/:id/apns",
+            put(handlers::key_apns_put).delete(handlers::key_apns_delete),
+        )
+        .route(
+            "/keys/:id/quiet-hours",
+            put(handlers::key_quiet_hours_put).delete(handlers::key_quiet_hours_delete),
+        )
+        .route(
+            "/keys/:id/webhook-subscriptions",
+            get(handlers::key_webhook_subscriptions_list)
+                .post(handlers::key_webhook_subscription_create),
+        )
+        .route(
+            "/keys/:id/webhook-subscriptions/:sub_id",
+            delete(handlers::key_webhook_subscription_delete),
+        )
+        .route("/webhooks", get(handlers::webhooks_list).post(handlers::webhook_create))
+        .route(
+            "/webhooks/:id",
+            put(handlers::webhook_update).delete(handlers::webhook_delete),
+        )
+        .route("/webhooks/:id/deliveries", get(handlers::webhook_deliveries))
+        .route("/sinks", get(handlers::sinks_list).post(handlers::sink_create))
+        .route(
+            "/sinks/:id",
+            put(handlers::sink_update).delete(handlers::sink_delete),
+        )
+        .route(
+            "/frequency-caps",
+            get(handlers::frequency_caps_list).post(handlers::frequency_cap_create),
+        )
+        .route(
+            "/frequency-caps/:id",
+            put(handlers::frequency_cap_update).delete(handlers::frequency_cap_delete),
+        )
+        .route("/hooks", get(handlers::hooks_list).post(handlers::hook_create))
+        .route(
+            "/hooks/:id",
+            put(handlers::hook_update).delete(handlers::hook_delete),
+        )
+        .route("/keys/:id/recipients", get(handlers::recipients_list))
+        .route(
+            "/keys/:id/recipients/:user_id",
+            put(handlers::recipient_put).delete(handlers::recipient_delete),
+        )
+        .route(
+            "/notifications/:id/deliveries",
+            get(handlers::notification_deliveries),
+        )
+        .route("/channels", get(handlers::channels_list))
+        .route(
+            "/channels/:name",
+            get(handlers::channel_show).delete(handlers::channel_delete),
+        )
+        .route("/channels/:name/members", get(handlers::channel_members));
     let app = Router::new()
         .route("/vapid-public-key", get(handlers::vapid_public_key))
+        .route("/signing-public-key", get(handlers::signing_public_key))
         .route("/subscribe", post(handlers::subscribe))
+        .route("/unsubscribe", post(handlers::unsubscribe))
+        .route("/interactions", post(handlers::interaction))
         .route("/notify", post(handlers::notify))
         .route("/notify/last", get(handlers::notify_last))
+        .route("/payloads/:id", get(handlers::payload_get))
         .route("/trigger", post(handlers::trigger))
+        .route("/trigger/batch", post(handlers::trigger_batch))
+        .route("/stream", get(handlers::stream))
+        .route("/stream/subscribe", post(handlers::stream_subscribe))
+        .route("/app/:key", get(handlers::pusher_ws))
+        .route("/hooks/:app/:adapter", post(inbound_hooks::receive))
+        .route(
+            "/message",
+            get(gotify::list_messages).post(gotify::create_message),
+        )
+        .route("/apps/:app_id/events", post(pusher_api::events))
+        .route("/apps/:app_id/batch_events", post(pusher_api::batch_events))
+        .route("/apps/:app_id/channels", get(pusher_api::channels))
         .route(
             "/api/login",
             post(handlers::login).options(|| async { StatusCode::NO_CONTENT }),
@@ -46,6 +155,17 @@ async fn main() -> anyhow::Result<()> {
         )
         .nest("/api", api_protected)
         .nest_service("/static", ServeDir::new("static"))
+        // Publish gaya ntfy; GET tetap ke file statis.
+        .route(
+            "/",
+            post(ntfy::publish_json).fallback_service(ServeDir::new("static")),
+        )
+        .route(
+            "/:topic",
+            post(ntfy::publish)
+                .put(ntfy::publish)
+                .fallback_service(ServeDir::new("static")),
+        )
         .fallback_service(ServeDir::new("static"))
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
         .with_state(state);
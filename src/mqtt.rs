@@ -0,0 +1,198 @@
+//! Bridge MQTT masuk (cargo feature `mqtt`): subscribe ke broker dan teruskan pesan
+//! sebagai trigger, supaya alert perangkat IoT menjadi push di browser.
+//!
+//! Konfigurasi lewat env (bridge aktif jika `MQTT_HOST` di-set):
+//! - `MQTT_HOST`, `MQTT_PORT` (1883), `MQTT_CLIENT_ID` (`push-notif`),
+//!   `MQTT_USERNAME` / `MQTT_PASSWORD` (opsional).
+//! - `MQTT_TOPICS`: daftar `filter[=channel]` dipisah koma, mis.
+//!   `sensors/+/alert=alerts,devices/#`. Tanpa `=channel`, topic pesan dipakai sebagai nama
+//!   channel (`/` juga pemisah segmen channel, lihat `crate::channel`).
+//! - `MQTT_EVENT`: nama event trigger (`mqtt`).
+//!
+//! Payload JSON object dipakai sebagai `data` (title default = topic, body dari `body` atau
+//! `message`); payload lain menjadi `body`. Coba lokal: `mosquitto -v` lalu
+//! `mosquitto_pub -t sensors/kitchen/alert -m '{"title":"Asap","body":"Dapur"}'`.
+
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::channel;
+use crate::handlers::{publish_trigger, TriggerBody};
+use crate::state::AppState;
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_CLIENT_ID: &str = "push-notif";
+const DEFAULT_EVENT: &str = "mqtt";
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Satu filter topic MQTT dan channel tujuannya (None = pakai topic pesan).
+#[derive(Clone)]
+struct Route {
+    filter: String,
+    channel: Option<String>,
+}
+
+#[derive(Clone)]
+struct Config {
+    host: String,
+    port: u16,
+    client_id: String,
+    credentials: Option<(String, String)>,
+    routes: Vec<Route>,
+    event: String,
+}
+
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn parse_routes(spec: &str) -> Result<Vec<Route>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(|r| {
+            let (filter, channel) = match r.split_once('=') {
+                Some((f, c)) => (f.trim(), Some(c.trim().to_string())),
+                None => (r, None),
+            };
+            if let Some(ch) = &channel {
+                channel::validate_name(ch)?;
+            }
+            Ok(Route {
+                filter: filter.to_string(),
+                channel,
+            })
+        })
+        .collect()
+}
+
+fn config_from_env() -> Result<Option<Config>, String> {
+    let Some(host) = env("MQTT_HOST") else {
+        return Ok(None);
+    };
+    let port = match env("MQTT_PORT") {
+        Some(p) => p
+            .parse()
+            .map_err(|_| format!("MQTT_PORT tidak valid: {}", p))?,
+        None => DEFAULT_PORT,
+    };
+    let routes = parse_routes(&env("MQTT_TOPICS").unwrap_or_default())?;
+    if routes.is_empty() {
+        return Err("MQTT_TOPICS wajib diisi".to_string());
+    }
+    Ok(Some(Config {
+        host,
+        port,
+        client_id: env("MQTT_CLIENT_ID").unwrap_or_else(|| DEFAULT_CLIENT_ID.to_string()),
+        credentials: env("MQTT_USERNAME").map(|u| (u, env("MQTT_PASSWORD").unwrap_or_default())),
+        routes,
+        event: env("MQTT_EVENT").unwrap_or_else(|| DEFAULT_EVENT.to_string()),
+    }))
+}
+
+/// Pencocokan filter MQTT (`+` satu level, `#` sisa level).
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for f in filter.split('/') {
+        match (f, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (f, Some(t)) if f == t => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+fn trigger_data(topic: &str, payload: &[u8]) -> serde_json::Value {
+    let text = String::from_utf8_lossy(payload);
+    let mut data = match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(obj @ serde_json::Value::Object(_)) => obj,
+        _ => serde_json::json!({ "body": text.trim() }),
+    };
+    if data.get("body").is_none() {
+        if let Some(message) = data.get("message").cloned() {
+            data["body"] = message;
+        }
+    }
+    if data.get("title").is_none() {
+        data["title"] = topic.into();
+    }
+    data["topic"] = topic.into();
+    data
+}
+
+async fn forward(state: &AppState, config: &Config, topic: &str, payload: &[u8]) {
+    let Some(route) = config
+        .routes
+        .iter()
+        .find(|r| topic_matches(&r.filter, topic))
+    else {
+        return;
+    };
+    let channel = route.channel.clone().unwrap_or_else(|| topic.to_string());
+    let trigger = TriggerBody {
+        channels: vec![channel.clone()],
+        channels_all: Vec::new(),
+        exclude_channels: Vec::new(),
+        exclude: Vec::new(),
+        event: config.event.clone(),
+        data: trigger_data(topic, payload),
+        fetch_on_push: None,
+        urgency: None,
+        app_id: None,
+    };
+    match publish_trigger(state, &trigger).await {
+        Ok(outcome) => {
+            info!(topic, channel = %channel, sent = outcome.sent, "mqtt message forwarded")
+        }
+        Err(e) => warn!(topic, channel = %channel, error = %e.message, "mqtt message rejected"),
+    }
+}
+
+async fn run(state: AppState, config: Config) {
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    if let Some((username, password)) = &config.credentials {
+        options.set_credentials(username, password);
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 64);
+    loop {
+        match eventloop.poll().await {
+            // Session bersih: subscribe ulang setiap kali (re)connect.
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                info!(host = %config.host, port = config.port, "mqtt connected");
+                for route in &config.routes {
+                    if let Err(e) = client.subscribe(&route.filter, QoS::AtLeastOnce).await {
+                        warn!(filter = %route.filter, error = %e, "mqtt subscribe failed");
+                    }
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                let state = state.clone();
+                let config = config.clone();
+                // Kirim push di task terpisah agar event loop (keep-alive) tidak tertahan.
+                tokio::spawn(async move {
+                    forward(&state, &config, &publish.topic, &publish.payload).await;
+                });
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(error = %e, "mqtt connection error, reconnecting");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Jalankan bridge di background jika `MQTT_HOST` di-set.
+pub fn spawn(state: AppState) -> anyhow::Result<()> {
+    let Some(config) = config_from_env().map_err(anyhow::Error::msg)? else {
+        return Ok(());
+    };
+    info!(host = %config.host, routes = config.routes.len(), "mqtt bridge enabled");
+    tokio::spawn(run(state, config));
+    Ok(())
+}
@@ -0,0 +1,238 @@
+//! Publish API kompatibel ntfy (https://docs.ntfy.sh/publish/), supaya skrip
+//! `curl -d "pesan" server/topic` bisa dipakai tanpa perubahan.
+//!
+//! - `PUT/POST /:topic`: body = pesan; opsi lewat header `Title`, `Priority`, `Tags`, `Click`
+//!   (atau `X-…`, singkatan `t`/`p`/`ta`, atau query `?title=` dst).
+//! - `POST /` dengan JSON `{topic, message, title, priority, tags, click}`.
+//!
+//! Topic = nama channel. Priority 1-5 dipetakan ke header `Urgency` Web Push. Path yang sudah
+//! dipakai route lain (`/subscribe`, `/message` Gotify, dst) tidak bisa dipakai sebagai topic.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use std::collections::HashMap;
+use web_push::Urgency;
+
+use crate::handlers::{publish_trigger, TriggerBody};
+use crate::state::AppState;
+
+/// Event channel untuk pesan ntfy.
+const NTFY_EVENT: &str = "message";
+const DEFAULT_MESSAGE: &str = "triggered";
+const DEFAULT_PRIORITY: u8 = 3;
+const TOPIC_MAX_LEN: usize = 64;
+
+#[derive(Deserialize)]
+pub struct JsonMessage {
+    pub topic: String,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub click: Option<String>,
+}
+
+struct Message {
+    topic: String,
+    message: String,
+    title: Option<String>,
+    priority: u8,
+    tags: Vec<String>,
+    click: Option<String>,
+}
+
+/// Topic ntfy: `[-_A-Za-z0-9]{1,64}`.
+fn valid_topic(topic: &str) -> bool {
+    !topic.is_empty()
+        && topic.len() <= TOPIC_MAX_LEN
+        && topic
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn parse_priority(s: &str) -> Option<u8> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "1" | "min" => Some(1),
+        "2" | "low" => Some(2),
+        "3" | "default" => Some(3),
+        "4" | "high" => Some(4),
+        "5" | "max" | "urgent" => Some(5),
+        _ => None,
+    }
+}
+
+fn urgency(priority: u8) -> Urgency {
+    match priority {
+        1 => Urgency::VeryLow,
+        2 => Urgency::Low,
+        3 => Urgency::Normal,
+        _ => Urgency::High,
+    }
+}
+
+/// Nilai parameter dari header (nama mana pun) atau query.
+fn param(headers: &HeaderMap, query: &HashMap<String, String>, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|n| headers.get(*n).and_then(|v| v.to_str().ok()))
+        .map(str::to_string)
+        .or_else(|| {
+            names
+                .iter()
+                .find_map(|n| query.get(&n.to_ascii_lowercase()).cloned())
+        })
+        .filter(|v| !v.is_empty())
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    // Format error ntfy.
+    (
+        status,
+        Json(
+            serde_json::json!({ "code": status.as_u16(), "http": status.as_u16(), "error": message }),
+        ),
+    )
+}
+
+/// ID pesan gaya ntfy (12 karakter alfanumerik).
+fn message_id() -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let mut bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+        .iter()
+        .map(|b| CHARS[*b as usize % CHARS.len()] as char)
+        .collect()
+}
+
+async fn send(state: &AppState, msg: Message) -> (StatusCode, Json<serde_json::Value>) {
+    if !valid_topic(&msg.topic) {
+        return error(StatusCode::NOT_FOUND, "invalid topic");
+    }
+    let id = message_id();
+    let time = chrono::Utc::now().timestamp();
+    let trigger = TriggerBody {
+        channels: vec![msg.topic.clone()],
+        channels_all: Vec::new(),
+        exclude_channels: Vec::new(),
+        exclude: Vec::new(),
+        event: NTFY_EVENT.to_string(),
+        data: serde_json::json!({
+            "id": id,
+            "title": msg.title.clone().unwrap_or_else(|| msg.topic.clone()),
+            "body": msg.message,
+            "url": msg.click,
+            "tags": msg.tags,
+            "priority": msg.priority
+        }),
+        fetch_on_push: None,
+        urgency: Some(urgency(msg.priority)),
+        app_id: None,
+    };
+    if let Err(e) = publish_trigger(state, &trigger).await {
+        return error(e.status, &e.message);
+    }
+    let mut response = serde_json::json!({
+        "id": id,
+        "time": time,
+        "event": NTFY_EVENT,
+        "topic": msg.topic,
+        "message": msg.message
+    });
+    if let Some(title) = msg.title {
+        response["title"] = title.into();
+    }
+    if msg.priority != DEFAULT_PRIORITY {
+        response["priority"] = msg.priority.into();
+    }
+    if !msg.tags.is_empty() {
+        response["tags"] = msg.tags.into();
+    }
+    if let Some(click) = msg.click {
+        response["click"] = click.into();
+    }
+    (StatusCode::OK, Json(response))
+}
+
+/// `PUT/POST /:topic`
+pub async fn publish(
+    State(state): State<AppState>,
+    Path(topic): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let priority = match param(&headers, &query, &["X-Priority", "Priority", "Prio", "p"]) {
+        Some(p) => match parse_priority(&p) {
+            Some(p) => p,
+            None => return error(StatusCode::BAD_REQUEST, "invalid priority"),
+        },
+        None => DEFAULT_PRIORITY,
+    };
+    let message = param(&headers, &query, &["X-Message", "Message", "m"])
+        .or_else(|| {
+            let text = String::from_utf8_lossy(&body).trim().to_string();
+            Some(text).filter(|t| !t.is_empty())
+        })
+        .unwrap_or_else(|| DEFAULT_MESSAGE.to_string());
+    let tags = param(&headers, &query, &["X-Tags", "Tags", "Tag", "ta"])
+        .map(|t| {
+            t.split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let msg = Message {
+        topic,
+        message,
+        title: param(&headers, &query, &["X-Title", "Title", "ti", "t"]),
+        priority,
+        tags,
+        click: param(&headers, &query, &["X-Click", "Click"]),
+    };
+    send(&state, msg).await
+}
+
+/// `POST /` dengan body JSON.
+pub async fn publish_json(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let json: JsonMessage = match serde_json::from_slice(&body) {
+        Ok(j) => j,
+        Err(_) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "invalid request: body must be JSON",
+            )
+        }
+    };
+    let priority = json.priority.unwrap_or(DEFAULT_PRIORITY);
+    if !(1..=5).contains(&priority) {
+        return error(StatusCode::BAD_REQUEST, "invalid priority");
+    }
+    let msg = Message {
+        topic: json.topic,
+        message: json
+            .message
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string()),
+        title: json.title.filter(|t| !t.is_empty()),
+        priority,
+        tags: json.tags,
+        click: json.click.filter(|c| !c.is_empty()),
+    };
+    send(&state, msg).await
+}
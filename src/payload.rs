@@ -0,0 +1,105 @@
+//! Batas ukuran payload Web Push dan mode "fetch-on-push".
+//!
+//! Push service menolak body terenkripsi di atas ~4 KB, dan crate web-push menolak
+//! plaintext di atas 3052 byte. Ukuran terenkripsi aes128gcm (RFC 8188, satu record):
+//! header 86 byte (salt 16 + rs 4 + idlen 1 + keyid 65) + plaintext + delimiter 1 + tag 16.
+//!
+//! Mode fetch-on-push: payload lengkap disimpan di tabel `push_payloads`, push hanya membawa
+//! `payload_id` (+ title/body terpotong), lalu `sw.js` mengambil payload dari `GET /payloads/:id`.
+
+use chrono::{Duration, Utc};
+use rand_core::{OsRng, RngCore};
+use sqlx::PgPool;
+
+const AES128GCM_OVERHEAD: usize = 86 + 1 + 16;
+/// Batas body terenkripsi yang diterima push service.
+pub const MAX_ENCRYPTED_SIZE: usize = 4096;
+/// Batas plaintext dari crate web-push (`WebPushError::PayloadTooLarge`).
+const MAX_PLAINTEXT_SIZE: usize = 3052;
+/// Lama payload fetch-on-push disimpan.
+const STORED_PAYLOAD_TTL: Duration = Duration::days(7);
+/// Panjang maksimum title/body di push ringkas fetch-on-push.
+pub const PREVIEW_CHARS: usize = 200;
+/// Panjang `payload_id` (hex 128-bit) dari `store`.
+pub const PAYLOAD_ID_LEN: usize = 32;
+
+/// Perkiraan ukuran payload setelah dienkripsi.
+pub fn encrypted_size(plaintext_len: usize) -> usize {
+    plaintext_len + AES128GCM_OVERHEAD
+}
+
+/// Plaintext terbesar yang bisa dikirim lewat Web Push.
+pub const MAX_PAYLOAD_SIZE: usize =
+    min(MAX_PLAINTEXT_SIZE, MAX_ENCRYPTED_SIZE - AES128GCM_OVERHEAD);
+
+const fn min(a: usize, b: usize) -> usize {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Ukuran plaintext Web Push untuk payload JSON ini, termasuk envelope signature
+/// (`crate::payload_signer`).
+pub fn push_size(payload: &str) -> usize {
+    crate::payload_signer::sealed_len(payload)
+}
+
+/// Apakah plaintext sebesar ini bisa dikirim lewat Web Push.
+pub fn fits(plaintext_len: usize) -> bool {
+    plaintext_len <= MAX_PAYLOAD_SIZE
+}
+
+/// Pesan error 413 untuk payload berukuran `plaintext_len`.
+pub fn too_large_message(plaintext_len: usize) -> String {
+    format!(
+        "Payload terlalu besar: {} byte setelah enkripsi (maks {} byte, plaintext maks {} byte). Perkecil data atau pakai fetch_on_push.",
+        encrypted_size(plaintext_len),
+        MAX_ENCRYPTED_SIZE,
+        MAX_PAYLOAD_SIZE
+    )
+}
+
+/// Potong string ke maksimal `max_bytes` byte tanpa memotong karakter UTF-8, tambah "…"
+/// (tanpa "…" jika `max_bytes` lebih kecil dari panjangnya).
+pub fn truncate(s: &str, max_bytes: usize) -> String {
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+    let ellipsis = if max_bytes >= "…".len() { "…" } else { "" };
+    let mut end = max_bytes - ellipsis.len();
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &s[..end], ellipsis)
+}
+
+/// Simpan payload lengkap untuk fetch-on-push. Return ID acak (tidak bisa ditebak).
+pub async fn store(pool: &PgPool, payload: &serde_json::Value) -> anyhow::Result<String> {
+    sqlx::query("DELETE FROM push_payloads WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+    let mut bytes = [0u8; PAYLOAD_ID_LEN / 2];
+    OsRng.fill_bytes(&mut bytes);
+    let id = hex::encode(bytes);
+    sqlx::query("INSERT INTO push_payloads (id, payload, expires_at) VALUES ($1, $2, $3)")
+        .bind(&id)
+        .bind(payload.to_string())
+        .bind(Utc::now() + STORED_PAYLOAD_TTL)
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn fetch(pool: &PgPool, id: &str) -> anyhow::Result<Option<serde_json::Value>> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT payload FROM push_payloads WHERE id = $1 AND expires_at >= NOW()")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?;
+    match row {
+        Some((payload,)) => Ok(Some(serde_json::from_str(&payload)?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,85 @@
+//! Tanda tangan end-to-end untuk payload Web Push, supaya `sw.js` bisa memastikan push
+//! benar-benar dari server ini (bukan dari push relay / proxy yang disusupi).
+//!
+//! Push dikirim sebagai envelope `{"payload": "<json>", "signature": "<base64url>"}`:
+//! ECDSA P-256 + SHA-256 atas byte `payload`, signature format r||s (64 byte) seperti
+//! yang diharapkan WebCrypto.
+//!
+//! Subscription milik app ditandatangani dengan private key app (`keys.key`), sehingga public
+//! key-nya = `keys.public_key` (dipin di `sw.js?signingKey=`). Subscription tanpa app memakai
+//! key server dari env `PUSH_SIGNING_KEY` (base64, format sama dengan kolom `keys.key`), atau
+//! yang dibuat sekali lalu disimpan di `PAYLOAD_SIGNING_KEY_FILE`. Keduanya dipublikasikan di
+//! `GET /signing-public-key[?app_id=]`.
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand_core::OsRng;
+use serde::Serialize;
+use std::path::Path;
+use tracing::info;
+
+const PAYLOAD_SIGNING_KEY_FILE: &str = "payload-signing.key";
+/// Panjang signature r||s (64 byte) dalam base64url tanpa padding.
+const SIGNATURE_B64_LEN: usize = 86;
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    payload: &'a str,
+    signature: String,
+}
+
+pub struct PayloadSigner {
+    key: SigningKey,
+}
+
+impl PayloadSigner {
+    /// Key server (untuk subscription tanpa app).
+    pub fn load() -> anyhow::Result<Self> {
+        let encoded = match std::env::var("PUSH_SIGNING_KEY") {
+            Ok(k) => k,
+            Err(_) => load_or_create_key_file(Path::new(PAYLOAD_SIGNING_KEY_FILE))?,
+        };
+        Self::from_base64(&encoded)
+    }
+
+    /// Private key base64 (format kolom `keys.key`).
+    pub fn from_base64(encoded: &str) -> anyhow::Result<Self> {
+        let bytes = STANDARD.decode(encoded.trim())?;
+        let key = SigningKey::from_slice(&bytes)
+            .map_err(|e| anyhow::anyhow!("invalid payload signing key: {}", e))?;
+        Ok(Self { key })
+    }
+
+    pub fn public_key_base64url(&self) -> String {
+        let point = self.key.verifying_key().to_encoded_point(false);
+        URL_SAFE_NO_PAD.encode(point.as_bytes())
+    }
+
+    /// Bungkus payload JSON menjadi envelope bertanda tangan.
+    pub fn seal(&self, payload: &str) -> String {
+        let signature: Signature = self.key.sign(payload.as_bytes());
+        let envelope = Envelope {
+            payload,
+            signature: URL_SAFE_NO_PAD.encode(signature.to_bytes()),
+        };
+        serde_json::to_string(&envelope).expect("envelope serializable")
+    }
+}
+
+/// Ukuran envelope hasil `seal` tanpa perlu menandatangani.
+pub fn sealed_len(payload: &str) -> usize {
+    let quoted = serde_json::to_string(payload).map(|s| s.len()).unwrap_or(0);
+    r#"{"payload":,"signature":""}"#.len() + quoted + SIGNATURE_B64_LEN
+}
+
+fn load_or_create_key_file(path: &Path) -> anyhow::Result<String> {
+    if path.exists() {
+        return Ok(std::fs::read_to_string(path)?);
+    }
+    let key = SigningKey::random(&mut OsRng);
+    let encoded = STANDARD.encode(key.to_bytes());
+    std::fs::write(path, &encoded)?;
+    info!(path = %path.display(), "payload signing key generated");
+    Ok(encoded)
+}
@@ -0,0 +1,220 @@
+//! Registry presence channel (`presence-*`) gaya Pusher: siapa saja yang sedang online
+//! lewat koneksi realtime (SSE / WebSocket).
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::channel::ChannelFilter;
+use crate::realtime::{RealtimeEvent, RealtimeHub};
+
+pub const PRESENCE_PREFIX: &str = "presence-";
+pub const PRIVATE_PREFIX: &str = "private-";
+
+/// Channel yang butuh auth signature saat subscribe.
+pub fn requires_auth(channel: &str) -> bool {
+    channel.starts_with(PRESENCE_PREFIX) || channel.starts_with(PRIVATE_PREFIX)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Member {
+    pub user_id: String,
+    pub user_info: serde_json::Value,
+}
+
+/// Parse `channel_data` dari client: `{"user_id": ..., "user_info": {...}}`.
+/// `user_id` boleh string atau angka.
+pub fn parse_channel_data(channel_data: &str) -> Result<Member, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(channel_data).map_err(|_| "channel_data bukan JSON".to_string())?;
+    let user_id = match value.get("user_id") {
+        Some(serde_json::Value::String(s)) if !s.is_empty() => s.clone(),
+        Some(serde_json::Value::Number(n)) => n.to_string(),
+        _ => return Err("channel_data.user_id wajib diisi".to_string()),
+    };
+    let user_info = value
+        .get("user_info")
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    Ok(Member { user_id, user_info })
+}
+
+struct MemberEntry {
+    user_info: serde_json::Value,
+    /// Satu user bisa online dari beberapa koneksi; dihitung sekali.
+    sockets: HashSet<String>,
+}
+
+/// user_id -> member.
+type Members = BTreeMap<String, MemberEntry>;
+
+/// (app, channel) -> member. Nama channel hanya unik di dalam satu app.
+#[derive(Clone, Default)]
+pub struct PresenceRegistry {
+    inner: Arc<Mutex<HashMap<(i32, String), Members>>>,
+}
+
+impl PresenceRegistry {
+    /// Daftarkan socket sebagai member. Return true jika user baru online di channel ini.
+    fn join(&self, app_id: i32, channel: &str, socket_id: &str, member: &Member) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let members = inner.entry((app_id, channel.to_string())).or_default();
+        match members.get_mut(&member.user_id) {
+            Some(entry) => {
+                entry.user_info = member.user_info.clone();
+                entry.sockets.insert(socket_id.to_string());
+                false
+            }
+            None => {
+                members.insert(
+                    member.user_id.clone(),
+                    MemberEntry {
+                        user_info: member.user_info.clone(),
+                        sockets: HashSet::from([socket_id.to_string()]),
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Lepas socket dari channel. Return user_id jika user tidak lagi online di channel ini.
+    fn leave(&self, app_id: i32, channel: &str, socket_id: &str) -> Option<String> {
+        let key = (app_id, channel.to_string());
+        let mut inner = self.inner.lock().unwrap();
+        let members = inner.get_mut(&key)?;
+        let mut user_id = None;
+        for (id, m) in members.iter_mut() {
+            if m.sockets.remove(socket_id) {
+                if m.sockets.is_empty() {
+                    user_id = Some(id.clone());
+                }
+                break;
+            }
+        }
+        if let Some(id) = &user_id {
+            members.remove(id);
+        }
+        if members.is_empty() {
+            inner.remove(&key);
+        }
+        user_id
+    }
+
+    /// Presence channel app yang punya minimal satu member online.
+    pub fn channels(&self, app_id: i32) -> Vec<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|(app, _)| *app == app_id)
+            .map(|(_, channel)| channel.clone())
+            .collect()
+    }
+
+    pub fn members(&self, app_id: i32, channel: &str) -> Vec<Member> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .get(&(app_id, channel.to_string()))
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|(id, m)| Member {
+                        user_id: id.clone(),
+                        user_info: m.user_info.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Data `subscription_succeeded` untuk presence channel (format Pusher).
+    pub fn subscription_data(&self, app_id: i32, channel: &str) -> serde_json::Value {
+        let members = self.members(app_id, channel);
+        let ids: Vec<&String> = members.iter().map(|m| &m.user_id).collect();
+        let hash: serde_json::Map<String, serde_json::Value> = members
+            .iter()
+            .map(|m| (m.user_id.clone(), m.user_info.clone()))
+            .collect();
+        serde_json::json!({
+            "presence": { "ids": ids, "hash": hash, "count": members.len() }
+        })
+    }
+
+    /// Join + umumkan `member_added` ke subscriber lain di channel.
+    pub fn join_and_announce(
+        &self,
+        hub: &RealtimeHub,
+        app_id: i32,
+        channel: &str,
+        socket_id: &str,
+        member: &Member,
+    ) {
+        if self.join(app_id, channel, socket_id, member) {
+            hub.publish(member_event(
+                "pusher_internal:member_added",
+                app_id,
+                channel,
+                socket_id,
+                serde_json::json!({ "user_id": member.user_id, "user_info": member.user_info }),
+            ));
+        }
+    }
+
+    /// Leave + umumkan `member_removed` jika user sudah tidak punya koneksi lain.
+    pub fn leave_and_announce(
+        &self,
+        hub: &RealtimeHub,
+        app_id: i32,
+        channel: &str,
+        socket_id: &str,
+    ) {
+        if let Some(user_id) = self.leave(app_id, channel, socket_id) {
+            hub.publish(member_event(
+                "pusher_internal:member_removed",
+                app_id,
+                channel,
+                socket_id,
+                serde_json::json!({ "user_id": user_id }),
+            ));
+        }
+    }
+}
+
+fn member_event(
+    event: &str,
+    app_id: i32,
+    channel: &str,
+    socket_id: &str,
+    data: serde_json::Value,
+) -> RealtimeEvent {
+    RealtimeEvent {
+        event: event.to_string(),
+        channel: channel.to_string(),
+        filter: ChannelFilter {
+            any: vec![channel.to_string()],
+            ..Default::default()
+        },
+        data,
+        exclude: vec![socket_id.to_string()],
+        app_id: Some(app_id),
+    }
+}
+
+/// Melepas semua membership presence sebuah koneksi saat koneksi ditutup.
+pub struct PresenceGuard {
+    pub registry: PresenceRegistry,
+    pub hub: RealtimeHub,
+    pub app_id: i32,
+    pub socket_id: String,
+    pub channels: Vec<String>,
+}
+
+impl Drop for PresenceGuard {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            self.registry
+                .leave_and_announce(&self.hub, self.app_id, channel, &self.socket_id);
+        }
+    }
+}
@@ -1,21 +1,42 @@
+use async_trait::async_trait;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
+use futures_util::{stream, StreamExt};
+use sqlx::PgPool;
+use std::collections::HashMap;
 use std::io::BufReader;
 use std::path::Path;
-use tracing::{error, info};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::apns::Apns;
+use crate::fcm::Fcm;
+use crate::frequency_caps;
+use crate::payload_signer::PayloadSigner;
+use crate::quiet_hours;
+use crate::state::{save_subscriptions, AppState, StoredSubscription, SubscriptionKind};
+use crate::transport::{ErrorClass, Transport, TransportError};
+use crate::webhook_transport::WebhookTransport;
+use crate::webhooks;
 use web_push::{
-    ContentEncoding, IsahcWebPushClient, PartialVapidSignatureBuilder, SubscriptionInfo,
-    VapidSignatureBuilder, WebPushClient, WebPushMessageBuilder,
+    ContentEncoding, IsahcWebPushClient, PartialVapidSignatureBuilder, Urgency,
+    VapidSignatureBuilder, WebPushClient, WebPushError, WebPushMessageBuilder,
 };
 
 const VAPID_PRIVATE_PEM: &str = "private.pem";
+/// Percobaan per subscription untuk error `Retryable` (termasuk percobaan pertama).
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Subscription yang dikirimi bersamaan di `send_to_all`.
+const SEND_CONCURRENCY: usize = 32;
 
-pub struct PushService {
+/// Transport Web Push (VAPID) untuk browser.
+pub struct WebPush {
     vapid_builder: PartialVapidSignatureBuilder,
     client: IsahcWebPushClient,
 }
 
-impl PushService {
+impl WebPush {
     pub fn new() -> anyhow::Result<Self> {
         let path = Path::new(VAPID_PRIVATE_PEM);
         if !path.exists() {
@@ -26,10 +47,9 @@ impl PushService {
         }
         let file = std::fs::File::open(path)?;
         let vapid_builder = VapidSignatureBuilder::from_pem_no_sub(BufReader::new(file))?;
-        let client = IsahcWebPushClient::new()?;
         Ok(Self {
             vapid_builder,
-            client,
+            client: IsahcWebPushClient::new()?,
         })
     }
 
@@ -37,39 +57,306 @@ impl PushService {
         let bytes = self.vapid_builder.get_public_key();
         URL_SAFE_NO_PAD.encode(bytes)
     }
+}
 
-    pub async fn send(&self, subscription: &SubscriptionInfo, payload: &[u8]) -> Result<(), web_push::WebPushError> {
-        let sig_builder = self.vapid_builder.clone();
-        let vapid_sig = sig_builder
-            .add_sub_info(subscription)
-            .build()?;
+#[async_trait]
+impl Transport for WebPush {
+    async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        urgency: Option<Urgency>,
+    ) -> Result<(), TransportError> {
+        let subscription = subscription.to_subscription_info();
+        let result = async {
+            let vapid_sig = self
+                .vapid_builder
+                .clone()
+                .add_sub_info(&subscription)
+                .build()?;
 
-        let mut builder = WebPushMessageBuilder::new(subscription);
-        builder.set_payload(ContentEncoding::Aes128Gcm, payload);
-        builder.set_vapid_signature(vapid_sig);
+            let mut builder = WebPushMessageBuilder::new(&subscription);
+            builder.set_payload(ContentEncoding::Aes128Gcm, payload);
+            builder.set_vapid_signature(vapid_sig);
+            if let Some(urgency) = urgency {
+                builder.set_urgency(urgency);
+            }
 
-        self.client.send(builder.build()?).await
+            self.client.send(builder.build()?).await
+        }
+        .await;
+        result.map_err(|e| {
+            let class = match e {
+                // 404/410 dari push service: endpoint sudah tidak berlaku.
+                WebPushError::EndpointNotValid(_) | WebPushError::EndpointNotFound(_) => {
+                    ErrorClass::Expired
+                }
+                WebPushError::ServerError { .. } | WebPushError::Io(_) => ErrorClass::Retryable,
+                _ => ErrorClass::Permanent,
+            };
+            TransportError::new(class, e.to_string())
+        })
     }
 }
 
-pub async fn send_to_all(
-    push_service: &PushService,
-    subscriptions: &[SubscriptionInfo],
-    payload: &[u8],
-) -> (usize, usize) {
-    let mut ok = 0;
-    let mut fail = 0;
-    for sub in subscriptions {
-        match push_service.send(sub, payload).await {
-            Ok(()) => {
-                ok += 1;
-                info!(endpoint = %sub.endpoint, "push sent");
+pub struct PushService {
+    web_push: WebPush,
+    fcm: Fcm,
+    apns: Apns,
+    webhook: WebhookTransport,
+    signer: PayloadSigner,
+    db: PgPool,
+}
+
+impl PushService {
+    pub fn new(db: PgPool) -> anyhow::Result<Self> {
+        Ok(Self {
+            web_push: WebPush::new()?,
+            fcm: Fcm::new(db.clone())?,
+            apns: Apns::new(db.clone())?,
+            webhook: WebhookTransport::new()?,
+            signer: PayloadSigner::load()?,
+            db,
+        })
+    }
+
+    pub fn public_key_base64url(&self) -> String {
+        self.web_push.public_key_base64url()
+    }
+
+    /// Public key untuk verifikasi signature payload (lihat `crate::payload_signer`).
+    pub fn signing_public_key_base64url(&self) -> String {
+        self.signer.public_key_base64url()
+    }
+
+    /// Tanda tangani payload JSON dengan key server (penerima tanpa app).
+    pub fn seal(&self, payload: &str) -> String {
+        self.signer.seal(payload)
+    }
+
+    /// Signer per app dari private key `keys.key`. App yang tidak ada / key-nya rusak tidak
+    /// ikut (penerimanya memakai key server).
+    pub async fn app_signers(&self, app_ids: &[i32]) -> HashMap<i32, PayloadSigner> {
+        if app_ids.is_empty() {
+            return HashMap::new();
+        }
+        let rows: Vec<(i32, String)> = sqlx::query_as("SELECT id, key FROM keys WHERE id = ANY($1)")
+            .bind(app_ids)
+            .fetch_all(&self.db)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(error = %e, "load app signing keys failed");
+                Vec::new()
+            });
+        rows.into_iter()
+            .filter_map(|(id, key)| match PayloadSigner::from_base64(&key) {
+                Ok(signer) => Some((id, signer)),
+                Err(e) => {
+                    warn!(app_id = id, error = %e, "invalid app signing key");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Tanda tangani payload untuk app `app_id` (key app jika ada, selain itu key server).
+    pub async fn seal_for(&self, app_id: Option<i32>, payload: &str) -> String {
+        let signers = self.app_signers(app_id.as_slice()).await;
+        match app_id.and_then(|id| signers.get(&id)) {
+            Some(signer) => signer.seal(payload),
+            None => self.seal(payload),
+        }
+    }
+
+    /// Transport FCM (untuk invalidasi access token saat service account diganti).
+    pub fn fcm(&self) -> &Fcm {
+        &self.fcm
+    }
+
+    /// Transport APNs (untuk invalidasi provider token saat kredensial diganti).
+    pub fn apns(&self) -> &Apns {
+        &self.apns
+    }
+
+    /// Transport sesuai `type` subscription.
+    pub fn transport(&self, kind: SubscriptionKind) -> &dyn Transport {
+        match kind {
+            SubscriptionKind::WebPush => &self.web_push,
+            SubscriptionKind::Fcm => &self.fcm,
+            SubscriptionKind::Apns => &self.apns,
+            SubscriptionKind::Webhook => &self.webhook,
+        }
+    }
+
+    /// Kirim lewat transport subscription; error `Retryable` diulang dengan backoff.
+    pub async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        urgency: Option<Urgency>,
+    ) -> Result<(), TransportError> {
+        let transport = self.transport(subscription.kind);
+        let mut delay = RETRY_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            match transport.send(subscription, payload, urgency).await {
+                Err(e) if e.class == ErrorClass::Retryable && attempt < MAX_ATTEMPTS => {
+                    warn!(endpoint = %subscription.endpoint, attempt, error = %e, "push retry");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
             }
-            Err(e) => {
+        }
+    }
+}
+
+/// Hasil pengiriman ke satu subscription di `send_to_all`.
+enum Delivery<'a> {
+    Sent,
+    Capped,
+    Failed { expired: bool, endpoint: &'a str },
+}
+
+/// Hasil `send_to_all`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SendOutcome {
+    pub sent: usize,
+    pub failed: usize,
+    /// Ditunda karena quiet hours (lihat `crate::quiet_hours`).
+    pub deferred: usize,
+    /// Dilewati karena frequency cap (lihat `crate::frequency_caps`).
+    pub capped: usize,
+}
+
+/// Kirim `payload` (JSON, belum ditandatangani) ke semua subscription lewat transport
+/// masing-masing, maksimal `SEND_CONCURRENCY` sekaligus; payload ditandatangani dengan key app
+/// subscription (lihat `crate::payload_signer`). Subscription dengan error
+/// `Expired` (endpoint / token sudah tidak berlaku) dipangkas dan dilaporkan lewat webhook `subscription.pruned`.
+/// `urgency` = header `Urgency` Web Push (None = default push service, `normal`); di bawah
+/// `high`, subscription yang sedang quiet hours ditunda. `channels` = channel tujuan push,
+/// untuk frequency cap per channel.
+pub async fn send_to_all(
+    state: &AppState,
+    subscriptions: &[StoredSubscription],
+    payload: &str,
+    urgency: Option<Urgency>,
+    channels: &[String],
+) -> SendOutcome {
+    let mut app_ids: Vec<i32> = subscriptions.iter().filter_map(|s| s.app_id).collect();
+    app_ids.sort_unstable();
+    app_ids.dedup();
+    let signers = state.push_service.app_signers(&app_ids).await;
+    // Payload bertanda tangan per app (None = key server), dibuat sekali sebelum fan-out.
+    let mut sealed: HashMap<Option<i32>, Vec<u8>> = HashMap::new();
+    sealed.insert(None, state.push_service.seal(payload).into_bytes());
+    for (app_id, signer) in &signers {
+        sealed.insert(Some(*app_id), signer.seal(payload).into_bytes());
+    }
+    let deferrals = quiet_hours::deferrals(state, subscriptions, urgency).await;
+    let caps = frequency_caps::evaluate(state, subscriptions, channels).await;
+    let mut deferred = Vec::new();
+    let mut targets = Vec::new();
+    for (i, (sub, until)) in subscriptions.iter().zip(deferrals).enumerate() {
+        match until {
+            Some(until) => deferred.push((sub, until)),
+            None => targets.push((i, sub)),
+        }
+    }
+    let deliveries: Vec<_> = targets
+        .into_iter()
+        .map(|(i, sub)| {
+            let sealed_payload = &sealed[&sub.app_id.filter(|id| signers.contains_key(id))];
+            deliver(state, &caps, i, sub, sealed_payload, urgency)
+        })
+        .collect();
+    let results: Vec<Delivery> = stream::iter(deliveries)
+        .buffer_unordered(SEND_CONCURRENCY)
+        .collect()
+        .await;
+    let (mut ok, mut fail, mut capped) = (0, 0, 0);
+    let mut expired = Vec::new();
+    for result in results {
+        match result {
+            Delivery::Sent => ok += 1,
+            Delivery::Capped => capped += 1,
+            Delivery::Failed { expired: is_expired, endpoint } => {
                 fail += 1;
-                error!(endpoint = %sub.endpoint, error = %e, "push failed");
+                if is_expired {
+                    expired.push(endpoint);
+                }
             }
         }
     }
-    (ok, fail)
+    if !expired.is_empty() {
+        prune(state, &expired).await;
+    }
+    if !deferred.is_empty() {
+        info!(count = deferred.len(), "push deferred for quiet hours");
+        if let Err(e) = quiet_hours::defer(&state.db, &deferred, payload, urgency, channels).await {
+            error!(error = %e, "store deferred push failed");
+            fail += deferred.len();
+            deferred.clear();
+        }
+    }
+    SendOutcome {
+        sent: ok,
+        failed: fail,
+        deferred: deferred.len(),
+        capped,
+    }
+}
+
+/// Satu subscription di `send_to_all`: pesan slot frequency cap, kirim, lepas slot jika gagal.
+async fn deliver<'a>(
+    state: &AppState,
+    caps: &frequency_caps::Evaluation,
+    index: usize,
+    sub: &'a StoredSubscription,
+    payload: &[u8],
+    urgency: Option<Urgency>,
+) -> Delivery<'a> {
+    let Some(reservation) = frequency_caps::reserve(&state.db, caps, index, sub).await else {
+        info!(endpoint = %sub.endpoint, "push skipped, frequency cap reached");
+        return Delivery::Capped;
+    };
+    match state.push_service.send(sub, payload, urgency).await {
+        Ok(()) => {
+            info!(endpoint = %sub.endpoint, kind = ?sub.kind, "push sent");
+            Delivery::Sent
+        }
+        Err(e) => {
+            frequency_caps::release(&state.db, reservation).await;
+            error!(endpoint = %sub.endpoint, kind = ?sub.kind, class = ?e.class, error = %e, "push failed");
+            Delivery::Failed {
+                expired: e.class == ErrorClass::Expired,
+                endpoint: sub.endpoint.as_str(),
+            }
+        }
+    }
+}
+
+async fn prune(state: &AppState, endpoints: &[&str]) {
+    let mut subs = state.subscriptions.write().await;
+    let removed: Vec<_> = endpoints
+        .iter()
+        .filter_map(|endpoint| subs.remove_endpoint(endpoint))
+        .collect();
+    if removed.is_empty() {
+        return;
+    }
+    let to_save = subs.clone();
+    drop(subs);
+    if let Err(e) = save_subscriptions(&to_save).await {
+        warn!(error = %e, "failed to persist subscriptions");
+    }
+    for s in removed {
+        info!(endpoint = %s.endpoint, "expired subscription pruned");
+        state.webhooks.dispatch(
+            webhooks::SUBSCRIPTION_PRUNED,
+            s.app_id,
+            serde_json::json!({ "id": s.id, "endpoint": s.endpoint, "channels": s.channels }),
+        );
+    }
 }
@@ -0,0 +1,272 @@
+//! Lapisan kompatibilitas REST API Pusher Channels, supaya server SDK resmi Pusher
+//! bisa diarahkan ke service ini tanpa perubahan.
+//!
+//! - `app_id` = `id` di tabel keys, `auth_key` = `public_key`, secret = `secret`.
+//! - Event hanya dikirim ke subscription dan koneksi realtime milik app tersebut; `data`
+//!   diteruskan apa adanya (string).
+//! - Auth lewat query: `auth_key`, `auth_timestamp`, `auth_version`, `body_md5`, `auth_signature`
+//!   (HMAC-SHA256 atas `METHOD\nPATH\nquery_terurut`).
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::handlers::{publish_trigger, validate_trigger, TriggerBody, TriggerError};
+use crate::presence::PRESENCE_PREFIX;
+use crate::signature;
+use crate::state::AppState;
+
+/// Selisih maksimum `auth_timestamp` dengan jam server (detik), sama seperti Pusher.
+const AUTH_TIMESTAMP_GRACE: i64 = 600;
+/// Batas dari Pusher: channel per event dan event per batch.
+const MAX_EVENT_CHANNELS: usize = 100;
+const MAX_BATCH_EVENTS: usize = 10;
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(serde_json::json!({ "ok": false, "message": message.into() })),
+    )
+        .into_response()
+}
+
+/// Cek app + signature request. Return Err(response) jika ditolak.
+async fn authenticate(
+    state: &AppState,
+    app_id: i32,
+    method: &Method,
+    uri: &Uri,
+    query: &BTreeMap<String, String>,
+    body: &[u8],
+) -> Result<(), Response> {
+    let app: Option<(String, String)> =
+        sqlx::query_as("SELECT public_key, secret FROM keys WHERE id = $1")
+            .bind(app_id)
+            .fetch_optional(&state.db)
+            .await
+            .ok()
+            .flatten();
+    let Some((public_key, secret)) = app else {
+        return Err(error(StatusCode::NOT_FOUND, "App tidak ditemukan"));
+    };
+    if query.get("auth_key") != Some(&public_key) {
+        return Err(error(StatusCode::UNAUTHORIZED, "auth_key tidak valid"));
+    }
+    let timestamp: i64 = query
+        .get("auth_timestamp")
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(0);
+    if (chrono::Utc::now().timestamp() - timestamp).abs() > AUTH_TIMESTAMP_GRACE {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "auth_timestamp kedaluwarsa",
+        ));
+    }
+    if !body.is_empty() {
+        let body_md5 = hex::encode(Md5::digest(body));
+        if query.get("body_md5") != Some(&body_md5) {
+            return Err(error(StatusCode::UNAUTHORIZED, "body_md5 tidak cocok"));
+        }
+    }
+    let params: Vec<String> = query
+        .iter()
+        .filter(|(k, _)| k.as_str() != "auth_signature")
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    let to_sign = format!("{}\n{}\n{}", method, uri.path(), params.join("&"));
+    let signature = query
+        .get("auth_signature")
+        .map(String::as_str)
+        .unwrap_or("");
+    if !signature::verify(&secret, &to_sign, signature) {
+        return Err(error(
+            StatusCode::UNAUTHORIZED,
+            "auth_signature tidak valid",
+        ));
+    }
+    Ok(())
+}
+
+/// Query Pusher dengan key lowercase (Pusher memperlakukan key case-insensitive).
+fn normalize_query(query: HashMap<String, String>) -> BTreeMap<String, String> {
+    query
+        .into_iter()
+        .map(|(k, v)| (k.to_lowercase(), v))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct PusherEvent {
+    pub name: String,
+    /// Pusher mengirim data sebagai string (biasanya JSON).
+    pub data: String,
+    #[serde(default)]
+    pub channels: Vec<String>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    /// Socket pengirim yang tidak ikut menerima event.
+    #[serde(default)]
+    pub socket_id: Option<String>,
+}
+
+impl PusherEvent {
+    /// Trigger untuk app `app_id`: hanya subscription / koneksi app itu yang menerima.
+    fn into_trigger(self, app_id: i32) -> Result<TriggerBody, String> {
+        let mut channels = self.channels;
+        channels.extend(self.channel);
+        if channels.is_empty() {
+            return Err("channels wajib diisi".to_string());
+        }
+        if channels.len() > MAX_EVENT_CHANNELS {
+            return Err(format!("maksimal {} channel per event", MAX_EVENT_CHANNELS));
+        }
+        Ok(TriggerBody {
+            channels,
+            channels_all: Vec::new(),
+            exclude_channels: Vec::new(),
+            exclude: self.socket_id.into_iter().collect(),
+            event: self.name,
+            // Diteruskan apa adanya (string), seperti Pusher: klien yang mem-parse.
+            data: serde_json::Value::String(self.data),
+            fetch_on_push: None,
+            urgency: None,
+            app_id: Some(app_id),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct BatchEvents {
+    pub batch: Vec<PusherEvent>,
+}
+
+/// `POST /apps/:app_id/events`
+pub async fn events(
+    State(state): State<AppState>,
+    Path(app_id): Path<i32>,
+    method: Method,
+    uri: Uri,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let query = normalize_query(query);
+    if let Err(rejected) = authenticate(&state, app_id, &method, &uri, &query, &body).await {
+        return rejected;
+    }
+    let event: PusherEvent = match serde_json::from_slice(&body) {
+        Ok(e) => e,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let trigger = match event.into_trigger(app_id) {
+        Ok(t) => t,
+        Err(message) => return error(StatusCode::BAD_REQUEST, message),
+    };
+    match publish_trigger(&state, &trigger).await {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({}))).into_response(),
+        Err(e) => error(e.status, e.message),
+    }
+}
+
+/// `POST /apps/:app_id/batch_events`
+pub async fn batch_events(
+    State(state): State<AppState>,
+    Path(app_id): Path<i32>,
+    method: Method,
+    uri: Uri,
+    Query(query): Query<HashMap<String, String>>,
+    body: Bytes,
+) -> Response {
+    let query = normalize_query(query);
+    if let Err(rejected) = authenticate(&state, app_id, &method, &uri, &query, &body).await {
+        return rejected;
+    }
+    let batch: BatchEvents = match serde_json::from_slice(&body) {
+        Ok(b) => b,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if batch.batch.len() > MAX_BATCH_EVENTS {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("maksimal {} event per batch", MAX_BATCH_EVENTS),
+        );
+    }
+    // Validasi semua event dulu supaya batch tidak terkirim sebagian.
+    let triggers: Result<Vec<TriggerBody>, TriggerError> = batch
+        .batch
+        .into_iter()
+        .map(|e| {
+            let trigger = e.into_trigger(app_id)?;
+            validate_trigger(&trigger)?;
+            Ok(trigger)
+        })
+        .collect();
+    let triggers = match triggers {
+        Ok(t) => t,
+        Err(e) => return error(e.status, e.message),
+    };
+    for trigger in &triggers {
+        if let Err(e) = publish_trigger(&state, trigger).await {
+            return error(e.status, e.message);
+        }
+    }
+    (StatusCode::OK, Json(serde_json::json!({}))).into_response()
+}
+
+/// `GET /apps/:app_id/channels` — channel app ini yang punya subscriber atau member presence.
+pub async fn channels(
+    State(state): State<AppState>,
+    Path(app_id): Path<i32>,
+    method: Method,
+    uri: Uri,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let query = normalize_query(query);
+    if let Err(rejected) = authenticate(&state, app_id, &method, &uri, &query, &[]).await {
+        return rejected;
+    }
+    let prefix = query
+        .get("filter_by_prefix")
+        .map(String::as_str)
+        .unwrap_or("");
+    let want_user_count = query
+        .get("info")
+        .is_some_and(|info| info.split(',').any(|i| i == "user_count"));
+    if want_user_count && !prefix.starts_with(PRESENCE_PREFIX) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "info=user_count hanya untuk filter_by_prefix=presence-",
+        );
+    }
+
+    let mut names: Vec<String> = {
+        let subs = state.subscriptions.read().await;
+        subs.channel_counts(Some(app_id)).into_keys().collect()
+    };
+    names.extend(state.presence.channels(app_id));
+    names.sort();
+    names.dedup();
+    let channels: serde_json::Map<String, serde_json::Value> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| {
+            let info = if want_user_count {
+                serde_json::json!({ "user_count": state.presence.members(app_id, &name).len() })
+            } else {
+                serde_json::json!({})
+            };
+            (name, info)
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "channels": channels })),
+    )
+        .into_response()
+}
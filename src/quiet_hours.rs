@@ -0,0 +1,292 @@
+//! Quiet hours (jangan ganggu) per app dan per subscriber.
+//!
+//! - Jendela `{start, end, timezone}`: jam lokal `HH:MM` dan zona IANA (mis. `Asia/Jakarta`).
+//!   `end` < `start` = melewati tengah malam (`22:00`–`07:00`); `start` == `end` = nonaktif.
+//! - Per app di tabel `quiet_hours` (`PUT /api/keys/:id/quiet-hours`), berlaku untuk
+//!   subscription dengan `app_id` tersebut; per subscriber lewat `quiet_hours` di `/subscribe`.
+//! - Push dengan urgency di bawah `high` (termasuk tanpa urgency) ke subscription yang sedang
+//!   quiet hours disimpan di `deferred_pushes` sampai jendela berakhir (yang paling akhir jika
+//!   keduanya aktif), lalu dikirim oleh scheduler. Urgency `high` selalu langsung dikirim.
+//! - Zona waktu memakai database tz PostgreSQL (`AT TIME ZONE`), termasuk pergantian DST.
+
+use chrono::{DateTime, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{info, warn};
+use web_push::Urgency;
+
+use crate::push_service;
+use crate::state::{AppState, StoredSubscription};
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+/// Maksimum push tertunda yang diambil per putaran scheduler.
+const SCHEDULER_BATCH: i64 = 500;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: String,
+}
+
+impl QuietHours {
+    pub fn enabled(&self) -> bool {
+        self.start != self.end
+    }
+
+    /// Akhir jendela (jam lokal) jika `now` (jam lokal) berada di dalamnya.
+    fn window_end(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (today, t) = (now.date(), now.time());
+        if self.start < self.end {
+            (self.start <= t && t < self.end).then(|| today.and_time(self.end))
+        } else if self.start > self.end && t >= self.start {
+            today.succ_opt().map(|d| d.and_time(self.end))
+        } else if self.start > self.end && t < self.end {
+            Some(today.and_time(self.end))
+        } else {
+            None
+        }
+    }
+}
+
+/// Validasi jendela; zona waktu harus dikenal PostgreSQL.
+pub async fn validate(db: &PgPool, q: &QuietHours) -> Result<(), String> {
+    if q.timezone.trim().is_empty() {
+        return Err("quiet_hours.timezone wajib diisi".to_string());
+    }
+    let known: Option<(String,)> =
+        sqlx::query_as("SELECT name FROM pg_timezone_names WHERE name = $1")
+            .bind(&q.timezone)
+            .fetch_optional(db)
+            .await
+            .map_err(|e| e.to_string())?;
+    match known {
+        Some(_) => Ok(()),
+        None => Err(format!("timezone tidak dikenal: {}", q.timezone)),
+    }
+}
+
+/// Jendela quiet hours per app (`keys.id`).
+async fn app_windows(db: &PgPool, app_ids: &[i32]) -> sqlx::Result<HashMap<i32, QuietHours>> {
+    let rows: Vec<(i32, NaiveTime, NaiveTime, String)> = sqlx::query_as(
+        "SELECT key_id, start_time, end_time, timezone FROM quiet_hours WHERE key_id = ANY($1)",
+    )
+    .bind(app_ids)
+    .fetch_all(db)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(key_id, start, end, timezone)| {
+            (
+                key_id,
+                QuietHours {
+                    start,
+                    end,
+                    timezone,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Jam lokal sekarang per zona waktu.
+async fn local_now(
+    db: &PgPool,
+    zones: Vec<String>,
+) -> sqlx::Result<HashMap<String, NaiveDateTime>> {
+    let rows: Vec<(String, NaiveDateTime)> =
+        sqlx::query_as("SELECT tz, NOW() AT TIME ZONE tz FROM unnest($1::TEXT[]) AS tz")
+            .bind(zones)
+            .fetch_all(db)
+            .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Jam lokal (per zona) ke UTC, urutan sesuai input.
+async fn to_utc(
+    db: &PgPool,
+    local: &[(String, NaiveDateTime)],
+) -> sqlx::Result<Vec<DateTime<Utc>>> {
+    let (zones, times): (Vec<String>, Vec<NaiveDateTime>) = local.iter().cloned().unzip();
+    let rows: Vec<(DateTime<Utc>,)> = sqlx::query_as(
+        "SELECT t.local AT TIME ZONE t.tz FROM unnest($1::TIMESTAMP[], $2::TEXT[]) WITH ORDINALITY AS t(local, tz, i) ORDER BY t.i",
+    )
+    .bind(times)
+    .bind(zones)
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(|(t,)| t).collect())
+}
+
+/// Waktu kirim untuk tiap subscription (urutan sama): `None` = kirim sekarang.
+/// Gagal membaca database = kirim sekarang (quiet hours tidak boleh menahan push selamanya).
+pub async fn deferrals(
+    state: &AppState,
+    subscriptions: &[StoredSubscription],
+    urgency: Option<Urgency>,
+) -> Vec<Option<DateTime<Utc>>> {
+    let mut result = vec![None; subscriptions.len()];
+    if urgency == Some(Urgency::High) {
+        return result;
+    }
+    match compute_deferrals(&state.db, subscriptions).await {
+        Ok(until) => result = until,
+        Err(e) => warn!(error = %e, "quiet hours lookup failed, sending immediately"),
+    }
+    result
+}
+
+async fn compute_deferrals(
+    db: &PgPool,
+    subscriptions: &[StoredSubscription],
+) -> sqlx::Result<Vec<Option<DateTime<Utc>>>> {
+    let mut app_ids: Vec<i32> = subscriptions.iter().filter_map(|s| s.app_id).collect();
+    app_ids.sort_unstable();
+    app_ids.dedup();
+    let apps = if app_ids.is_empty() {
+        HashMap::new()
+    } else {
+        app_windows(db, &app_ids).await?
+    };
+    let windows: Vec<Vec<&QuietHours>> = subscriptions
+        .iter()
+        .map(|s| {
+            s.quiet_hours
+                .iter()
+                .chain(s.app_id.and_then(|id| apps.get(&id)))
+                .filter(|q| q.enabled())
+                .collect()
+        })
+        .collect();
+    let mut zones: Vec<String> = windows
+        .iter()
+        .flatten()
+        .map(|q| q.timezone.clone())
+        .collect();
+    if zones.is_empty() {
+        return Ok(vec![None; subscriptions.len()]);
+    }
+    zones.sort();
+    zones.dedup();
+    let now = local_now(db, zones).await?;
+
+    // Akhir jendela aktif (zona, jam lokal) per subscription, lalu konversi sekaligus ke UTC.
+    let mut ends: Vec<(String, NaiveDateTime)> = Vec::new();
+    let per_sub: Vec<Vec<usize>> = windows
+        .iter()
+        .map(|ws| {
+            ws.iter()
+                .filter_map(|q| {
+                    let end = q.window_end(*now.get(&q.timezone)?)?;
+                    let key = (q.timezone.clone(), end);
+                    Some(ends.iter().position(|e| *e == key).unwrap_or_else(|| {
+                        ends.push(key);
+                        ends.len() - 1
+                    }))
+                })
+                .collect()
+        })
+        .collect();
+    if ends.is_empty() {
+        return Ok(vec![None; subscriptions.len()]);
+    }
+    let ends_utc = to_utc(db, &ends).await?;
+    Ok(per_sub
+        .into_iter()
+        .map(|idx| {
+            idx.into_iter()
+                .filter_map(|i| ends_utc.get(i).copied())
+                .max()
+        })
+        .collect())
+}
+
+/// Simpan push (payload belum ditandatangani) untuk dikirim scheduler pada `deliver_at`.
+pub async fn defer(
+    db: &PgPool,
+    deferred: &[(&StoredSubscription, DateTime<Utc>)],
+    payload: &str,
+    urgency: Option<Urgency>,
+    channels: &[String],
+) -> sqlx::Result<()> {
+    let (ids, times): (Vec<String>, Vec<DateTime<Utc>>) =
+        deferred.iter().map(|(s, t)| (s.id.clone(), *t)).unzip();
+    sqlx::query(
+        "INSERT INTO deferred_pushes (subscription_id, payload, urgency, channels, deliver_at) SELECT id, $3, $4, $5, t FROM unnest($1::TEXT[], $2::TIMESTAMPTZ[]) AS d(id, t)",
+    )
+    .bind(ids)
+    .bind(times)
+    .bind(payload)
+    .bind(urgency.map(|u| u.to_string()))
+    .bind(channels)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Scheduler push tertunda: setiap `SCHEDULER_INTERVAL` kirim yang sudah jatuh tempo.
+pub fn spawn_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = run_due(&state).await {
+                warn!(error = %e, "deferred push run failed");
+            }
+        }
+    });
+}
+
+async fn run_due(state: &AppState) -> sqlx::Result<()> {
+    let rows: Vec<(String, String, Option<String>, Vec<String>)> = sqlx::query_as(
+        "DELETE FROM deferred_pushes WHERE id IN (SELECT id FROM deferred_pushes WHERE deliver_at <= NOW() ORDER BY deliver_at LIMIT $1 FOR UPDATE SKIP LOCKED) RETURNING subscription_id, payload, urgency, channels",
+    )
+    .bind(SCHEDULER_BATCH)
+    .fetch_all(&state.db)
+    .await?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+    // Kelompokkan per (payload, urgency, channels) supaya satu notifikasi dikirim sebagai satu
+    // fan-out.
+    type Group = (String, Option<String>, Vec<String>);
+    let mut groups: HashMap<Group, Vec<String>> = HashMap::new();
+    for (subscription_id, payload, urgency, channels) in rows {
+        groups
+            .entry((payload, urgency, channels))
+            .or_default()
+            .push(subscription_id);
+    }
+    for ((payload, urgency, channels), ids) in groups {
+        let subscriptions: Vec<StoredSubscription> = {
+            let subs = state.subscriptions.read().await;
+            subs.subscriptions
+                .iter()
+                .filter(|s| ids.contains(&s.id))
+                .cloned()
+                .collect()
+        };
+        if subscriptions.is_empty() {
+            continue;
+        }
+        let urgency = urgency.and_then(|u| serde_json::from_value(u.into()).ok());
+        let outcome = push_service::send_to_all(
+            state,
+            &subscriptions,
+            &payload,
+            urgency,
+            &channels,
+        )
+        .await;
+        info!(
+            sent = outcome.sent,
+            failed = outcome.failed,
+            deferred = outcome.deferred,
+            capped = outcome.capped,
+            "deferred push delivered"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::channel::ChannelFilter;
+
+/// Kapasitas buffer broadcast; koneksi yang tertinggal lebih dari ini akan melewatkan event.
+const REALTIME_BUFFER: usize = 1024;
+
+/// Event yang dipublish oleh trigger ke koneksi realtime.
+#[derive(Clone, Debug)]
+pub struct RealtimeEvent {
+    pub event: String,
+    /// Label channel seperti di payload push ("broadcast", nama channel, atau "multi").
+    pub channel: String,
+    pub filter: ChannelFilter,
+    pub data: serde_json::Value,
+    /// Socket ID yang tidak menerima event (mis. socket pengirim).
+    pub exclude: Vec<String>,
+    /// App pengirim (API Pusher); jika ada, hanya koneksi milik app ini yang menerima.
+    pub app_id: Option<i32>,
+}
+
+impl RealtimeEvent {
+    /// Apakah event ini perlu dikirim ke koneksi `socket_id` milik `app_id` dengan pola
+    /// `channels`.
+    pub fn is_for(&self, socket_id: &str, app_id: Option<i32>, channels: &[String]) -> bool {
+        self.app_id.is_none_or(|app| app_id == Some(app))
+            && !self.exclude.iter().any(|s| s == socket_id)
+            && self.filter.accepts(channels)
+    }
+
+    /// Payload JSON yang sama dengan payload Web Push dari trigger.
+    pub fn payload(&self) -> serde_json::Value {
+        serde_json::json!({
+            "event": self.event,
+            "channel": self.channel,
+            "data": self.data
+        })
+    }
+}
+
+/// Hub publish/subscribe untuk koneksi realtime (SSE / WebSocket).
+#[derive(Clone)]
+pub struct RealtimeHub {
+    tx: broadcast::Sender<Arc<RealtimeEvent>>,
+}
+
+impl Default for RealtimeHub {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(REALTIME_BUFFER);
+        Self { tx }
+    }
+}
+
+impl RealtimeHub {
+    /// Kirim event ke semua koneksi; tiap koneksi memfilter sesuai channel-nya.
+    pub fn publish(&self, event: RealtimeEvent) {
+        // Error hanya berarti belum ada koneksi yang mendengarkan.
+        let _ = self.tx.send(Arc::new(event));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<RealtimeEvent>> {
+        self.tx.subscribe()
+    }
+}
+
+/// Koneksi SSE aktif per socket ID: app (dari `?app=`, di-resolve server) dan channel
+/// private/presence yang sudah diotorisasi lewat `POST /stream/subscribe`.
+#[derive(Clone, Default)]
+pub struct StreamRegistry {
+    inner: Arc<Mutex<HashMap<String, StreamConn>>>,
+}
+
+struct StreamConn {
+    app_id: Option<i32>,
+    channels: Vec<String>,
+}
+
+impl StreamRegistry {
+    pub fn register(&self, socket_id: &str, app_id: Option<i32>) {
+        self.inner.lock().unwrap().insert(
+            socket_id.to_string(),
+            StreamConn {
+                app_id,
+                channels: Vec::new(),
+            },
+        );
+    }
+
+    /// Hapus koneksi; return app dan channel private/presence-nya.
+    pub fn unregister(&self, socket_id: &str) -> Option<(Option<i32>, Vec<String>)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .remove(socket_id)
+            .map(|c| (c.app_id, c.channels))
+    }
+
+    /// App koneksi; `None` jika socket ID tidak terdaftar.
+    pub fn app(&self, socket_id: &str) -> Option<Option<i32>> {
+        self.inner.lock().unwrap().get(socket_id).map(|c| c.app_id)
+    }
+
+    /// Tambah channel yang sudah diotorisasi. Return false jika socket sudah tidak terhubung.
+    pub fn add_channel(&self, socket_id: &str, channel: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(conn) = inner.get_mut(socket_id) else {
+            return false;
+        };
+        if !conn.channels.iter().any(|c| c == channel) {
+            conn.channels.push(channel.to_string());
+        }
+        true
+    }
+
+    /// Apakah `event` ditujukan ke salah satu channel private/presence koneksi ini.
+    pub fn accepts(&self, socket_id: &str, event: &RealtimeEvent) -> bool {
+        self.inner.lock().unwrap().get(socket_id).is_some_and(|c| {
+            !c.channels.is_empty() && event.is_for(socket_id, c.app_id, &c.channels)
+        })
+    }
+}
@@ -0,0 +1,122 @@
+//! Penerima notifikasi per user: alamat email untuk fallback (user yang menolak izin
+//! notifikasi) dan catatan hasil pengiriman per penerima. `user_id` = ID user di aplikasi,
+//! sama seperti yang dikirim saat `/subscribe`, unik per app (`keys.id`).
+//!
+//! `user_id` di `/subscribe` harus disertai `user_auth` = HMAC-SHA256 hex atas `user_id` dengan
+//! secret app (dibuat backend aplikasi, seperti auth channel private), supaya subscriber tidak
+//! bisa mengaku sebagai user lain.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+pub const CHANNEL_PUSH: &str = "push";
+pub const CHANNEL_EMAIL: &str = "email";
+
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_SKIPPED: &str = "skipped";
+/// Push ditunda karena quiet hours.
+pub const STATUS_DEFERRED: &str = "deferred";
+/// Push dilewati karena frequency cap.
+pub const STATUS_CAPPED: &str = "capped";
+
+const USER_ID_MAX_LEN: usize = 255;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct RecipientRow {
+    pub key_id: i32,
+    pub user_id: String,
+    pub email: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpsertRecipientBody {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DeliveryRow {
+    pub id: i64,
+    pub history_id: i64,
+    pub user_id: String,
+    pub channel: String,
+    pub status: String,
+    pub detail: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub fn validate_user_id(user_id: &str) -> Result<(), String> {
+    if user_id.trim().is_empty() || user_id.len() > USER_ID_MAX_LEN {
+        return Err(format!(
+            "user_id wajib diisi (maks {} karakter)",
+            USER_ID_MAX_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Validasi ringan: satu `@`, tanpa spasi / baris baru.
+pub fn validate_email(email: &str) -> Result<(), String> {
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.contains('@')
+                && !email
+                    .chars()
+                    .any(|c| c.is_whitespace() || c == '<' || c == '>')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err("Alamat email tidak valid".to_string())
+    }
+}
+
+/// Cek `user_auth` untuk `user_id` dengan secret app.
+pub fn verify_user_auth(secret: &str, user_id: &str, user_auth: &str) -> bool {
+    crate::signature::verify(secret, user_id, user_auth)
+}
+
+pub async fn email_for(pool: &PgPool, key_id: i32, user_id: &str) -> sqlx::Result<Option<String>> {
+    let row: Option<(String,)> =
+        sqlx::query_as("SELECT email FROM recipients WHERE key_id = $1 AND user_id = $2")
+            .bind(key_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(row.map(|(email,)| email))
+}
+
+pub async fn record_delivery(
+    pool: &PgPool,
+    history_id: i64,
+    user_id: &str,
+    channel: &str,
+    status: &str,
+    detail: Option<&str>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO notification_deliveries (history_id, user_id, channel, status, detail) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(history_id)
+    .bind(user_id)
+    .bind(channel)
+    .bind(status)
+    .bind(detail)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn deliveries(pool: &PgPool, history_id: i64) -> sqlx::Result<Vec<DeliveryRow>> {
+    sqlx::query_as(
+        "SELECT id, history_id, user_id, channel, status, detail, created_at FROM notification_deliveries WHERE history_id = $1 ORDER BY id",
+    )
+    .bind(history_id)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,47 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signature HMAC-SHA256 dalam hex.
+pub fn sign(secret: &str, message: &str) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC menerima key sepanjang apa pun");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Verifikasi signature HMAC-SHA256 hex gaya Pusher (constant time).
+/// `secret` = kolom `secret` di tabel keys.
+pub fn verify(secret: &str, message: &str, signature_hex: &str) -> bool {
+    let Ok(signature) = hex::decode(signature_hex) else {
+        return false;
+    };
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC menerima key sepanjang apa pun");
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Verifikasi auth channel private/presence: `auth` = `<public_key>:<signature>` atas
+/// `<socket_id>:<channel>[:<channel_data>]`.
+pub fn verify_channel_auth(
+    public_key: &str,
+    secret: &str,
+    auth: &str,
+    socket_id: &str,
+    channel: &str,
+    channel_data: Option<&str>,
+) -> bool {
+    let Some((key, signature)) = auth.split_once(':') else {
+        return false;
+    };
+    if key != public_key {
+        return false;
+    }
+    let message = match channel_data {
+        Some(data) => format!("{}:{}:{}", socket_id, channel, data),
+        None => format!("{}:{}", socket_id, channel),
+    };
+    verify(secret, &message, signature)
+}
@@ -0,0 +1,116 @@
+//! Sink channel: URL incoming webhook (Slack / Discord / Teams / JSON) yang menerima pesan
+//! terformat setiap kali channel di-trigger, berjalan bersamaan dengan fan-out Web Push di
+//! `handlers::publish_trigger`.
+//!
+//! - Dikonfigurasi lewat `/api/sinks`; `channel` boleh pola wildcard (lihat `crate::channel`).
+//! - Sink milik app (`key_id`) hanya menerima trigger app tersebut dan payload ditandatangani
+//!   key app; sink tanpa app hanya menerima trigger tanpa app.
+//! - Pesan dibentuk oleh `webhook_transport::format_message` dari payload trigger lengkap
+//!   (tidak dibatasi ukuran push) dan dikirim lewat transport webhook, termasuk retry error
+//!   `Retryable` di `PushService::send`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use tracing::{info, warn};
+use web_push::Urgency;
+
+use crate::channel;
+use crate::state::{AppState, StoredSubscription, SubscriptionKeys, SubscriptionKind};
+use crate::webhook_transport::WebhookFormat;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SinkRow {
+    pub id: i32,
+    pub key_id: Option<i32>,
+    pub channel: String,
+    pub url: String,
+    pub format: String,
+    pub active: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSinkBody {
+    /// App (`keys.id`); kosong = sink untuk trigger tanpa app.
+    #[serde(default)]
+    pub key_id: Option<i32>,
+    pub channel: String,
+    pub url: String,
+    pub format: WebhookFormat,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSinkBody {
+    pub channel: Option<String>,
+    pub url: Option<String>,
+    pub format: Option<WebhookFormat>,
+    pub active: Option<bool>,
+}
+
+impl SinkRow {
+    /// Sink sebagai subscription `webhook`, supaya dikirim lewat `PushService::send`.
+    fn subscription(&self) -> StoredSubscription {
+        StoredSubscription {
+            id: format!("sink-{}", self.id),
+            kind: SubscriptionKind::Webhook,
+            endpoint: self.url.clone(),
+            keys: SubscriptionKeys::default(),
+            app_id: self.key_id,
+            user_id: None,
+            format: WebhookFormat::parse(&self.format),
+            quiet_hours: None,
+            channels: vec![self.channel.clone()],
+        }
+    }
+}
+
+/// Kirim payload trigger (belum ditandatangani) ke semua sink aktif app `app_id` yang cocok
+/// dengan salah satu `channels`. Tidak memblokir: dikirim di task terpisah.
+pub fn dispatch(
+    state: &AppState,
+    app_id: Option<i32>,
+    channels: Vec<String>,
+    payload: String,
+    urgency: Option<Urgency>,
+) {
+    if channels.is_empty() {
+        return;
+    }
+    let state = state.clone();
+    tokio::spawn(async move {
+        let rows: Vec<SinkRow> = match sqlx::query_as(
+            "SELECT id, key_id, channel, url, format, active, created_at FROM channel_sinks WHERE active AND key_id IS NOT DISTINCT FROM $1",
+        )
+        .bind(app_id)
+        .fetch_all(&state.db)
+        .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                warn!(error = %e, "load channel sinks failed");
+                return;
+            }
+        };
+        let rows: Vec<SinkRow> = rows
+            .into_iter()
+            .filter(|s| channels.iter().any(|c| channel::matches(&s.channel, c)))
+            .collect();
+        if rows.is_empty() {
+            return;
+        }
+        let payload = state.push_service.seal_for(app_id, &payload).await.into_bytes();
+        for sink in rows {
+            let state = state.clone();
+            let payload = payload.clone();
+            tokio::spawn(async move {
+                let sub = sink.subscription();
+                match state.push_service.send(&sub, &payload, urgency).await {
+                    Ok(()) => info!(sink_id = sink.id, channel = %sink.channel, "sink delivered"),
+                    Err(e) => {
+                        warn!(sink_id = sink.id, channel = %sink.channel, class = ?e.class, error = %e, "sink delivery failed")
+                    }
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,339 @@
+//! SMTP masuk (cargo feature `smtp`): sistem lama yang hanya bisa kirim email menjadi
+//! notifikasi. Email ke `<channel>@<app>.push.local` (`app` = `keys.id`) dikirim lewat
+//! logika `/notify` ke subscriber channel tersebut milik app itu: subject = title, isi teks =
+//! body.
+//!
+//! - Aktif jika env `SMTP_LISTEN` di-set (mis. `127.0.0.1:2525`); domain dari `SMTP_DOMAIN`
+//!   (default `push.local`).
+//! - Wajib `AUTH PLAIN` / `AUTH LOGIN` dengan password = `secret` app (username bebas). Tanpa
+//!   STARTTLS, jadi listen di localhost / jaringan tepercaya saja.
+//!
+//! Coba: `swaks --server 127.0.0.1:2525 --to alerts@1.push.local --auth-user x
+//! --auth-password <secret> --header "Subject: Backup gagal" --body "..."`.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use mail_parser::MessageParser;
+use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+use crate::channel;
+use crate::handlers::{send_notify, NotifyPayload};
+use crate::state::AppState;
+
+const DEFAULT_DOMAIN: &str = "push.local";
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+const MAX_LINE_LEN: usize = 1000;
+const MAX_RECIPIENTS: usize = 100;
+/// Potongan maksimal per pembacaan DATA.
+const DATA_CHUNK: u64 = 8 * 1024;
+
+#[derive(Clone)]
+struct Config {
+    listen: SocketAddr,
+    domain: String,
+}
+
+#[derive(Default)]
+struct Session {
+    helo: bool,
+    app_id: Option<i32>,
+    from: Option<String>,
+    channels: Vec<String>,
+}
+
+impl Session {
+    fn reset(&mut self) {
+        self.from = None;
+        self.channels.clear();
+    }
+}
+
+/// Baca satu baris (tanpa CRLF). None = koneksi ditutup atau baris terlalu panjang. Paling
+/// banyak `max + 1` byte yang dibaca, jadi klien tanpa newline tidak bisa menghabiskan memori.
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>, max: usize) -> Option<String> {
+    let mut buf = Vec::new();
+    let n = (&mut *reader)
+        .take(max as u64 + 1)
+        .read_until(b'\n', &mut buf)
+        .await
+        .ok()?;
+    if n == 0 || buf.len() > max {
+        return None;
+    }
+    while buf.last().is_some_and(|b| *b == b'\n' || *b == b'\r') {
+        buf.pop();
+    }
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Isi DATA sampai baris `.`, dengan dot-unstuffing. Inner None = melebihi batas ukuran.
+/// Dibaca per potongan maksimal `DATA_CHUNK` byte; baris yang lebih panjang disambung dari
+/// beberapa potongan.
+async fn read_data<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Option<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut too_large = false;
+    let mut line_start = true;
+    loop {
+        let mut chunk = Vec::new();
+        let n = (&mut *reader)
+            .take(DATA_CHUNK)
+            .read_until(b'\n', &mut chunk)
+            .await
+            .ok()?;
+        if n == 0 {
+            return None;
+        }
+        if line_start && (chunk == b".\r\n" || chunk == b".\n") {
+            break;
+        }
+        let part = if line_start {
+            chunk.strip_prefix(b".").unwrap_or(&chunk)
+        } else {
+            &chunk[..]
+        };
+        if data.len() + part.len() > MAX_MESSAGE_SIZE {
+            too_large = true;
+        } else {
+            data.extend_from_slice(part);
+        }
+        line_start = chunk.ends_with(b"\n");
+    }
+    Some((!too_large).then_some(data))
+}
+
+/// Alamat dari `MAIL FROM:<a@b>` / `RCPT TO:<a@b>`.
+fn address(arg: &str) -> Option<String> {
+    let start = arg.find('<')?;
+    let end = arg[start..].find('>')? + start;
+    Some(arg[start + 1..end].trim().to_string())
+}
+
+/// `<channel>@<app>.<domain>` -> (app_id, channel).
+fn parse_recipient(address: &str, domain: &str) -> Option<(i32, String)> {
+    let (channel, host) = address.rsplit_once('@')?;
+    let app = host
+        .to_ascii_lowercase()
+        .strip_suffix(&format!(".{}", domain.to_ascii_lowercase()))?
+        .to_string();
+    Some((app.parse().ok()?, channel.to_string()))
+}
+
+async fn app_for_secret(state: &AppState, secret: &str) -> Option<i32> {
+    sqlx::query_as::<_, (i32,)>("SELECT id FROM keys WHERE secret = $1")
+        .bind(secret)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .map(|(id,)| id)
+}
+
+fn decode_base64(s: &str) -> Option<String> {
+    let bytes = STANDARD.decode(s.trim()).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Password dari respons `AUTH PLAIN` (`authzid\0authcid\0password`).
+fn plain_password(encoded: &str) -> Option<String> {
+    decode_base64(encoded)?
+        .split('\0')
+        .nth(2)
+        .map(str::to_string)
+}
+
+/// Kirim email ke subscriber `channels` milik app yang terautentikasi.
+async fn deliver(state: AppState, app_id: i32, channels: Vec<String>, raw: Vec<u8>) {
+    let Some(message) = MessageParser::default().parse(&raw[..]) else {
+        warn!("smtp message could not be parsed");
+        return;
+    };
+    let title = message
+        .subject()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .unwrap_or("Email")
+        .to_string();
+    let body = message
+        .body_text(0)
+        .map(|b| b.trim().to_string())
+        .unwrap_or_default();
+    for channel in channels {
+        let payload = NotifyPayload {
+            title: title.clone(),
+            body: body.clone(),
+            icon: None,
+            badge: None,
+            sound: None,
+            channels: vec![channel.clone()],
+            users: Vec::new(),
+            fetch_on_push: None,
+            app_id: Some(app_id),
+        };
+        let (status, _) = send_notify(&state, payload).await;
+        info!(channel = %channel, status = %status, "smtp message delivered");
+    }
+}
+
+async fn session(state: AppState, config: Config, stream: TcpStream) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut reader = BufReader::new(read);
+    let mut s = Session::default();
+    write
+        .write_all(format!("220 {} ESMTP push-notif\r\n", config.domain).as_bytes())
+        .await?;
+    while let Some(line) = read_line(&mut reader, MAX_LINE_LEN).await {
+        let (verb, arg) = line.split_once(' ').unwrap_or((&line, ""));
+        let reply: String = match verb.to_ascii_uppercase().as_str() {
+            "EHLO" => {
+                s.helo = true;
+                s.reset();
+                format!(
+                    "250-{}\r\n250-SIZE {}\r\n250-8BITMIME\r\n250 AUTH PLAIN LOGIN\r\n",
+                    config.domain, MAX_MESSAGE_SIZE
+                )
+            }
+            "HELO" => {
+                s.helo = true;
+                s.reset();
+                format!("250 {}\r\n", config.domain)
+            }
+            "AUTH" => {
+                let (mechanism, initial) = arg.split_once(' ').unwrap_or((arg, ""));
+                let password = match mechanism.to_ascii_uppercase().as_str() {
+                    "PLAIN" => {
+                        let encoded = if initial.is_empty() {
+                            write.write_all(b"334 \r\n").await?;
+                            read_line(&mut reader, MAX_LINE_LEN)
+                                .await
+                                .unwrap_or_default()
+                        } else {
+                            initial.to_string()
+                        };
+                        plain_password(&encoded)
+                    }
+                    "LOGIN" => {
+                        // Username diabaikan; yang dicek hanya password (secret app).
+                        write.write_all(b"334 VXNlcm5hbWU6\r\n").await?;
+                        let _ = read_line(&mut reader, MAX_LINE_LEN).await;
+                        write.write_all(b"334 UGFzc3dvcmQ6\r\n").await?;
+                        read_line(&mut reader, MAX_LINE_LEN)
+                            .await
+                            .and_then(|p| decode_base64(&p))
+                    }
+                    _ => {
+                        write
+                            .write_all(b"504 5.5.4 Unrecognized authentication type\r\n")
+                            .await?;
+                        continue;
+                    }
+                };
+                match password {
+                    Some(p) => match app_for_secret(&state, &p).await {
+                        Some(app_id) => {
+                            s.app_id = Some(app_id);
+                            "235 2.7.0 Authentication successful\r\n".to_string()
+                        }
+                        None => "535 5.7.8 Authentication credentials invalid\r\n".to_string(),
+                    },
+                    None => "501 5.5.2 Invalid AUTH response\r\n".to_string(),
+                }
+            }
+            "MAIL" if !s.helo => "503 5.5.1 Send EHLO first\r\n".to_string(),
+            "MAIL" if s.app_id.is_none() => "530 5.7.0 Authentication required\r\n".to_string(),
+            "MAIL" => match address(arg) {
+                Some(from) => {
+                    s.reset();
+                    s.from = Some(from);
+                    "250 2.1.0 OK\r\n".to_string()
+                }
+                None => "501 5.1.7 Bad sender address\r\n".to_string(),
+            },
+            "RCPT" if s.from.is_none() => "503 5.5.1 Need MAIL first\r\n".to_string(),
+            "RCPT" if s.channels.len() >= MAX_RECIPIENTS => {
+                "452 4.5.3 Too many recipients\r\n".to_string()
+            }
+            "RCPT" => match address(arg).and_then(|a| parse_recipient(&a, &config.domain)) {
+                Some((app_id, _)) if Some(app_id) != s.app_id => {
+                    "550 5.7.1 Recipient app does not match credentials\r\n".to_string()
+                }
+                Some((_, ch)) if channel::validate_name(&ch).is_err() => {
+                    "553 5.1.3 Invalid channel name\r\n".to_string()
+                }
+                Some((_, ch)) => {
+                    s.channels.push(ch);
+                    "250 2.1.5 OK\r\n".to_string()
+                }
+                None => format!(
+                    "550 5.1.1 Recipient must be <channel>@<app>.{}\r\n",
+                    config.domain
+                ),
+            },
+            "DATA" if s.channels.is_empty() => "503 5.5.1 Need RCPT first\r\n".to_string(),
+            "DATA" => {
+                write
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+                let Some(data) = read_data(&mut reader).await else {
+                    break;
+                };
+                let channels = std::mem::take(&mut s.channels);
+                s.reset();
+                match (data, s.app_id) {
+                    (Some(raw), Some(app_id)) => {
+                        tokio::spawn(deliver(state.clone(), app_id, channels, raw));
+                        "250 2.0.0 Queued\r\n".to_string()
+                    }
+                    (Some(_), None) => "530 5.7.0 Authentication required\r\n".to_string(),
+                    (None, _) => "552 5.3.4 Message too big\r\n".to_string(),
+                }
+            }
+            "RSET" => {
+                s.reset();
+                "250 2.0.0 OK\r\n".to_string()
+            }
+            "NOOP" => "250 2.0.0 OK\r\n".to_string(),
+            "QUIT" => {
+                write.write_all(b"221 2.0.0 Bye\r\n").await?;
+                break;
+            }
+            _ => "502 5.5.2 Command not recognized\r\n".to_string(),
+        };
+        write.write_all(reply.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Jalankan listener SMTP di background jika `SMTP_LISTEN` di-set.
+pub async fn spawn(state: AppState) -> anyhow::Result<()> {
+    let Ok(listen) = std::env::var("SMTP_LISTEN") else {
+        return Ok(());
+    };
+    let config = Config {
+        listen: listen.parse()?,
+        domain: std::env::var("SMTP_DOMAIN").unwrap_or_else(|_| DEFAULT_DOMAIN.to_string()),
+    };
+    let listener = TcpListener::bind(config.listen).await?;
+    info!(addr = %config.listen, domain = %config.domain, "smtp ingress listening");
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!(error = %e, "smtp accept failed");
+                    continue;
+                }
+            };
+            let state = state.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                if let Err(e) = session(state, config, stream).await {
+                    warn!(%peer, error = %e, "smtp session error");
+                }
+            });
+        }
+    });
+    Ok(())
+}
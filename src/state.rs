@@ -1,29 +1,81 @@
+use chrono::{DateTime, Utc};
+use rand_core::{OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use web_push::SubscriptionInfo;
 
+use crate::channel::{self, ChannelFilter, ChannelIndex};
+use crate::mailer::Mailer;
+use crate::presence::PresenceRegistry;
 use crate::push_service::PushService;
+use crate::quiet_hours::QuietHours;
+use crate::realtime::{RealtimeHub, StreamRegistry};
+use crate::webhook_transport::WebhookFormat;
+use crate::webhooks::Webhooks;
 
 const SUBSCRIPTIONS_FILE: &str = "subscriptions.json";
 const DEFAULT_CHANNEL: &str = "default";
 
+/// Jenis subscription, menentukan transport pengiriman (lihat `crate::transport`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionKind {
+    /// Web Push browser (VAPID).
+    #[default]
+    WebPush,
+    /// Firebase Cloud Messaging; `endpoint` = registration token.
+    Fcm,
+    /// Apple Push Notification service; `endpoint` = device token.
+    Apns,
+    /// POST ke URL (`endpoint`), lihat `crate::webhook_transport`.
+    Webhook,
+}
+
 /// Satu subscription push + daftar channel (gaya Pusher).
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StoredSubscription {
+    /// ID acak, dipakai untuk `exclude` di trigger. Dibuat saat load jika kosong (data lama).
+    #[serde(default)]
+    pub id: String,
+    #[serde(default, rename = "type")]
+    pub kind: SubscriptionKind,
+    /// Endpoint Web Push, atau token perangkat untuk transport native.
     pub endpoint: String,
+    /// Kunci enkripsi Web Push (kosong untuk transport native).
+    #[serde(default)]
     pub keys: SubscriptionKeys,
+    /// App (tabel keys) pemilik kredensial transport native.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<i32>,
+    /// ID user di aplikasi, untuk notifikasi yang ditargetkan ke user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// Format body untuk subscription `webhook`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<WebhookFormat>,
+    /// Quiet hours subscriber (lihat `crate::quiet_hours`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
     #[serde(default)]
     pub channels: Vec<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SubscriptionKeys {
     pub p256dh: String,
     pub auth: String,
 }
 
+/// ID subscription acak (hex 16 byte).
+pub fn generate_subscription_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
 impl StoredSubscription {
     pub fn to_subscription_info(&self) -> SubscriptionInfo {
         SubscriptionInfo::new(
@@ -32,6 +84,12 @@ impl StoredSubscription {
             self.keys.auth.clone(),
         )
     }
+
+    /// `auth` sama dengan `keys.auth` subscription ini (perbandingan waktu konstan).
+    pub fn auth_matches(&self, auth: &str) -> bool {
+        auth.len() == self.keys.auth.len()
+            && openssl::memcmp::eq(auth.as_bytes(), self.keys.auth.as_bytes())
+    }
 }
 
 /// Format simpan: array subscriptions (mendukung backward compat load dari by_endpoint).
@@ -39,54 +97,219 @@ impl StoredSubscription {
 pub struct SubscriptionStore {
     #[serde(default)]
     pub subscriptions: Vec<StoredSubscription>,
+    /// Index pola channel -> posisi di `subscriptions` (tidak disimpan, dibangun ulang saat load).
+    #[serde(skip)]
+    index: ChannelIndex,
 }
 
 impl SubscriptionStore {
-    /// Menambah atau memperbarui subscription (merge channels by endpoint).
-    pub fn add(&mut self, endpoint: String, keys: SubscriptionKeys, channels: Vec<String>) {
-        let channels = if channels.is_empty() {
-            vec![DEFAULT_CHANNEL.to_string()]
-        } else {
-            channels
+    pub fn new(mut subscriptions: Vec<StoredSubscription>) -> Self {
+        for s in subscriptions.iter_mut().filter(|s| s.id.is_empty()) {
+            s.id = generate_subscription_id();
+        }
+        let mut store = Self {
+            subscriptions,
+            index: ChannelIndex::default(),
         };
-        if let Some(stored) = self.subscriptions.iter_mut().find(|s| s.endpoint == endpoint) {
-            stored.keys = keys;
-            for ch in channels {
+        store.rebuild_index();
+        store
+    }
+
+    fn rebuild_index(&mut self) {
+        let mut index = ChannelIndex::default();
+        for (i, s) in self.subscriptions.iter().enumerate() {
+            for ch in &s.channels {
+                index.insert(ch, i);
+            }
+        }
+        self.index = index;
+    }
+
+    /// Menambah atau memperbarui subscription (merge channels by endpoint). `new.id` diabaikan.
+    /// Return ID subscription.
+    ///
+    /// Endpoint yang sudah ada hanya di-merge jika jenis dan `keys.auth` sama dan app tidak
+    /// berpindah; selain itu return None tanpa mengubah apa pun, supaya pihak yang hanya tahu
+    /// endpoint tidak bisa mengganti keys enkripsi atau memindahkan perangkat ke app lain.
+    pub fn add(&mut self, mut new: StoredSubscription) -> Option<String> {
+        if new.channels.is_empty() {
+            new.channels = vec![DEFAULT_CHANNEL.to_string()];
+        }
+        if let Some(pos) = self.subscriptions.iter().position(|s| s.endpoint == new.endpoint) {
+            let stored = &mut self.subscriptions[pos];
+            let moves_app =
+                new.app_id.is_some() && stored.app_id.is_some() && new.app_id != stored.app_id;
+            if stored.kind != new.kind || !stored.auth_matches(&new.keys.auth) || moves_app {
+                return None;
+            }
+            // p256dh boleh diperbarui; auth sudah sama.
+            stored.keys = new.keys;
+            // Request tanpa app (mis. SDK lama) tidak melepas subscription dari app-nya.
+            if new.app_id.is_some() {
+                stored.app_id = new.app_id;
+            }
+            stored.format = new.format;
+            if new.user_id.is_some() {
+                stored.user_id = new.user_id;
+            }
+            if let Some(q) = new.quiet_hours {
+                stored.quiet_hours = Some(q).filter(QuietHours::enabled);
+            }
+            for ch in new.channels {
                 if !stored.channels.contains(&ch) {
+                    self.index.insert(&ch, pos);
                     stored.channels.push(ch);
                 }
             }
+            Some(stored.id.clone())
         } else {
-            self.subscriptions.push(StoredSubscription {
-                endpoint,
-                keys,
-                channels,
-            });
+            let pos = self.subscriptions.len();
+            for ch in &new.channels {
+                self.index.insert(ch, pos);
+            }
+            new.id = generate_subscription_id();
+            new.quiet_hours = new.quiet_hours.filter(QuietHours::enabled);
+            let id = new.id.clone();
+            self.subscriptions.push(new);
+            Some(id)
         }
     }
 
     /// Semua subscription (untuk broadcast / notify lama).
-    pub fn all(&self) -> Vec<SubscriptionInfo> {
-        self.subscriptions
-            .iter()
-            .map(StoredSubscription::to_subscription_info)
+    pub fn all(&self) -> Vec<StoredSubscription> {
+        self.subscriptions.clone()
+    }
+
+    /// Subscription yang lolos filter channel (nama konkret, dicocokkan ke pola
+    /// subscription termasuk wildcard, lihat `crate::channel`):
+    /// - `any`: minimal salah satu channel (kosong = tanpa batasan),
+    /// - `all`: harus berlangganan semua channel ini,
+    /// - `exclude`: dilewati jika berlangganan salah satu channel ini.
+    ///
+    /// Subscription dengan ID atau endpoint di `exclude` juga dilewati.
+    pub fn by_channel_filter(
+        &self,
+        filter: &ChannelFilter,
+        exclude: &[String],
+    ) -> Vec<StoredSubscription> {
+        let mut selected: BTreeSet<usize> = if filter.any.is_empty() {
+            (0..self.subscriptions.len()).collect()
+        } else {
+            let mut set = BTreeSet::new();
+            for ch in &filter.any {
+                self.index.lookup(ch, &mut set);
+            }
+            set
+        };
+        for ch in &filter.all {
+            let mut set = BTreeSet::new();
+            self.index.lookup(ch, &mut set);
+            selected.retain(|i| set.contains(i));
+        }
+        if !filter.exclude.is_empty() {
+            let mut set = BTreeSet::new();
+            for ch in &filter.exclude {
+                self.index.lookup(ch, &mut set);
+            }
+            selected.retain(|i| !set.contains(i));
+        }
+        selected
+            .into_iter()
+            .map(|i| &self.subscriptions[i])
+            .filter(|s| !exclude.iter().any(|e| *e == s.id || *e == s.endpoint))
+            .cloned()
+            .collect()
+    }
+
+    /// Subscription milik `user_id` di app `app_id` yang lolos filter channel.
+    pub fn for_user(
+        &self,
+        app_id: i32,
+        user_id: &str,
+        filter: &ChannelFilter,
+    ) -> Vec<StoredSubscription> {
+        self.by_channel_filter(filter, &[])
+            .into_iter()
+            .filter(|s| s.app_id == Some(app_id) && s.user_id.as_deref() == Some(user_id))
             .collect()
     }
 
-    /// Subscription yang berlangganan minimal salah satu channel yang diberikan.
-    pub fn by_channels(&self, channels: &[String]) -> Vec<SubscriptionInfo> {
-        if channels.is_empty() {
-            return self.all();
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Semua channel (pola seperti saat subscribe) beserta jumlah subscriber-nya.
+    /// `app_id` Some = hanya subscription app tersebut.
+    pub fn channel_counts(&self, app_id: Option<i32>) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for s in self
+            .subscriptions
+            .iter()
+            .filter(|s| app_id.is_none() || s.app_id == app_id)
+        {
+            for ch in &s.channels {
+                *counts.entry(ch.clone()).or_insert(0) += 1;
+            }
         }
+        counts
+    }
+
+    /// Pola channel subscription (seperti di `channel_counts`) yang cocok dengan channel
+    /// konkret `name`, termasuk wildcard.
+    pub fn matching_channels(&self, name: &str) -> BTreeSet<String> {
+        let mut positions = BTreeSet::new();
+        self.index.lookup(name, &mut positions);
+        positions
+            .into_iter()
+            .filter_map(|i| self.subscriptions.get(i))
+            .flat_map(|s| s.channels.iter().filter(|c| channel::matches(c, name)))
+            .cloned()
+            .collect()
+    }
+
+    /// Subscription yang berlangganan channel `name` (pencocokan persis, bukan wildcard).
+    pub fn in_channel(&self, name: &str) -> Vec<&StoredSubscription> {
         self.subscriptions
             .iter()
-            .filter(|s| s.channels.iter().any(|c| channels.contains(c)))
-            .map(StoredSubscription::to_subscription_info)
+            .filter(|s| s.channels.iter().any(|c| c == name))
             .collect()
     }
 
-    pub fn len(&self) -> usize {
-        self.subscriptions.len()
+    /// Hapus subscription berdasarkan endpoint. Return subscription yang dihapus.
+    pub fn remove_endpoint(&mut self, endpoint: &str) -> Option<StoredSubscription> {
+        self.remove_where(|s| s.endpoint == endpoint)
+    }
+
+    /// Hapus subscription pertama yang cocok dengan `pred`. Return subscription yang dihapus.
+    pub fn remove_where(
+        &mut self,
+        pred: impl Fn(&StoredSubscription) -> bool,
+    ) -> Option<StoredSubscription> {
+        let pos = self.subscriptions.iter().position(pred)?;
+        let removed = self.subscriptions.remove(pos);
+        self.rebuild_index();
+        Some(removed)
+    }
+
+    /// Hapus channel dari semua subscription. Subscription yang tidak lagi punya channel
+    /// kembali ke channel default (sama seperti subscribe tanpa channel), bukan dihapus.
+    /// Return jumlah subscription yang terdampak.
+    pub fn remove_channel(&mut self, name: &str) -> usize {
+        let mut removed = 0;
+        for s in &mut self.subscriptions {
+            let before = s.channels.len();
+            s.channels.retain(|c| c != name);
+            if s.channels.len() != before {
+                removed += 1;
+                if s.channels.is_empty() {
+                    s.channels.push(DEFAULT_CHANNEL.to_string());
+                }
+            }
+        }
+        if removed > 0 {
+            self.rebuild_index();
+        }
+        removed
     }
 }
 
@@ -101,34 +324,67 @@ pub struct LastNotification {
 pub struct AppState {
     pub push_service: Arc<PushService>,
     pub subscriptions: Arc<RwLock<SubscriptionStore>>,
+    /// Koneksi realtime (SSE / WebSocket) untuk halaman yang sedang terbuka.
+    pub realtime: RealtimeHub,
+    /// Member online di presence channel (`presence-*`).
+    pub presence: PresenceRegistry,
+    /// Koneksi SSE (`/stream`) beserta channel private/presence yang sudah diotorisasi.
+    pub streams: StreamRegistry,
     pub last_notification: Arc<RwLock<Option<LastNotification>>>,
+    /// Waktu trigger terakhir per channel (in-memory).
+    pub channel_last_triggered: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    /// Webhook keluar ke backend pemilik app (lihat `crate::webhooks`).
+    pub webhooks: Webhooks,
+    /// SMTP relay untuk fallback email (lihat `crate::mailer`).
+    pub mailer: Arc<Mailer>,
     pub db: PgPool,
     pub jwt_secret: Arc<[u8]>,
 }
 
 impl AppState {
     pub async fn new() -> anyhow::Result<Self> {
-        let push_service = PushService::new()?;
-        let subscriptions = load_subscriptions().await.unwrap_or_default();
         let db = crate::db::create_pool().await?;
+        let push_service = PushService::new(db.clone())?;
+        let subscriptions = load_subscriptions().await.unwrap_or_default();
         crate::db::run_migrations(&db).await?;
         crate::db::seed_admin_if_empty(&db).await?;
         let jwt_secret = std::env::var("JWT_SECRET")
             .unwrap_or_else(|_| "push-notif-secret-change-in-production".to_string());
         let jwt_secret = Arc::from(jwt_secret.as_bytes());
+        let webhooks = Webhooks::new(db.clone())?;
+        let mailer = Mailer::from_env()?;
         Ok(Self {
             push_service: Arc::new(push_service),
             subscriptions: Arc::new(RwLock::new(subscriptions)),
+            realtime: RealtimeHub::default(),
+            presence: PresenceRegistry::default(),
+            streams: StreamRegistry::default(),
             last_notification: Arc::new(RwLock::new(None)),
+            channel_last_triggered: Arc::new(RwLock::new(HashMap::new())),
+            webhooks,
+            mailer: Arc::new(mailer),
             db,
             jwt_secret,
         })
     }
+
+    /// Catat notifikasi terakhir (untuk `/notify/last`). Return ID berurutan.
+    pub async fn record_last_notification(&self, title: &str, body: &str) -> u64 {
+        let mut last = self.last_notification.write().await;
+        let id = last.as_ref().map(|n| n.id + 1).unwrap_or(1);
+        *last = Some(LastNotification {
+            id,
+            title: title.to_string(),
+            body: body.to_string(),
+        });
+        id
+    }
 }
 
 async fn load_subscriptions() -> anyhow::Result<SubscriptionStore> {
     let data = tokio::fs::read_to_string(SUBSCRIPTIONS_FILE).await?;
     let value: serde_json::Value = serde_json::from_str(&data)?;
+    let migrated;
     let store = if let Some(obj) = value.get("by_endpoint") {
         // Format lama: by_endpoint -> { url: SubscriptionInfo }
         let by_endpoint = obj.as_object().ok_or_else(|| anyhow::anyhow!("by_endpoint not object"))?;
@@ -139,16 +395,30 @@ async fn load_subscriptions() -> anyhow::Result<SubscriptionStore> {
                 let p256dh = keys.get("p256dh").and_then(|x| x.as_str()).unwrap_or("").to_string();
                 let auth = keys.get("auth").and_then(|x| x.as_str()).unwrap_or("").to_string();
                 subscriptions.push(StoredSubscription {
+                    id: String::new(),
+                    kind: SubscriptionKind::WebPush,
                     endpoint: ep.to_string(),
                     keys: SubscriptionKeys { p256dh, auth },
+                    app_id: None,
+                    user_id: None,
+                    format: None,
+                    quiet_hours: None,
                     channels: vec![DEFAULT_CHANNEL.to_string()],
                 });
             }
         }
-        SubscriptionStore { subscriptions }
+        migrated = !subscriptions.is_empty();
+        SubscriptionStore::new(subscriptions)
     } else {
-        serde_json::from_value(value).unwrap_or_default()
+        let store: SubscriptionStore = serde_json::from_value(value).unwrap_or_default();
+        migrated = store.subscriptions.iter().any(|s| s.id.is_empty());
+        SubscriptionStore::new(store.subscriptions)
     };
+    // ID yang baru dibuat untuk entri lama harus langsung disimpan, kalau tidak
+    // ID berubah tiap restart dan referensi klien (unsubscribe, preferensi) hilang.
+    if migrated {
+        save_subscriptions(&store).await?;
+    }
     Ok(store)
 }
 
@@ -160,3 +430,109 @@ pub async fn save_subscriptions(store: &SubscriptionStore) -> anyhow::Result<()>
     tokio::fs::write(SUBSCRIPTIONS_FILE, data).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(endpoint: &str, channels: &[&str]) -> StoredSubscription {
+        serde_json::from_value(serde_json::json!({
+            "endpoint": endpoint,
+            "keys": { "p256dh": "p256dh", "auth": "auth" },
+            "channels": channels
+        }))
+        .expect("subscription")
+    }
+
+    fn endpoints(store: &SubscriptionStore, any: &[&str]) -> Vec<String> {
+        let filter = ChannelFilter {
+            any: any.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        store
+            .by_channel_filter(&filter, &[])
+            .into_iter()
+            .map(|s| s.endpoint)
+            .collect()
+    }
+
+    fn store() -> SubscriptionStore {
+        let mut store = SubscriptionStore::default();
+        store.add(subscription("https://push/a", &["orders.#"]));
+        store.add(subscription(
+            "https://push/b",
+            &["orders.*.created", "news"],
+        ));
+        store.add(subscription("https://push/c", &["news"]));
+        store
+    }
+
+    #[test]
+    fn add_indexes_channel_patterns() {
+        let store = store();
+        assert_eq!(
+            endpoints(&store, &["orders.eu.created"]),
+            ["https://push/a", "https://push/b"]
+        );
+        assert_eq!(endpoints(&store, &["orders"]), ["https://push/a"]);
+        assert_eq!(
+            endpoints(&store, &["news"]),
+            ["https://push/b", "https://push/c"]
+        );
+        assert!(endpoints(&store, &["weather"]).is_empty());
+    }
+
+    #[test]
+    fn add_merges_new_channels_into_index() {
+        let mut store = store();
+        store.add(subscription("https://push/c", &["weather"]));
+        assert_eq!(store.len(), 3);
+        assert_eq!(endpoints(&store, &["weather"]), ["https://push/c"]);
+        assert_eq!(
+            endpoints(&store, &["news"]),
+            ["https://push/b", "https://push/c"]
+        );
+    }
+
+    #[test]
+    fn remove_where_reindexes_remaining_subscriptions() {
+        let mut store = store();
+        assert!(store
+            .remove_where(|s| s.endpoint == "https://push/a")
+            .is_some());
+        assert_eq!(
+            endpoints(&store, &["orders.eu.created"]),
+            ["https://push/b"]
+        );
+        assert!(endpoints(&store, &["orders"]).is_empty());
+        assert_eq!(
+            endpoints(&store, &["news"]),
+            ["https://push/b", "https://push/c"]
+        );
+    }
+
+    #[test]
+    fn remove_channel_falls_back_to_default() {
+        let mut store = store();
+        assert_eq!(store.remove_channel("news"), 2);
+        assert!(endpoints(&store, &["news"]).is_empty());
+        assert_eq!(endpoints(&store, &[DEFAULT_CHANNEL]), ["https://push/c"]);
+        assert_eq!(
+            endpoints(&store, &["orders.eu.created"]),
+            ["https://push/a", "https://push/b"]
+        );
+    }
+
+    #[test]
+    fn matching_channels_returns_subscribed_patterns() {
+        let store = store();
+        assert_eq!(
+            store
+                .matching_channels("orders.eu.created")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            ["orders.#", "orders.*.created"]
+        );
+        assert!(store.matching_channels("weather").is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Transport pengiriman push. Setiap subscription punya `type` (lihat
+//! `crate::state::SubscriptionKind`) yang menentukan transport-nya; `push_service::send_to_all`
+//! memilih transport lewat `PushService::transport`, mengulang error `Retryable`, dan memangkas
+//! subscription yang `Expired`.
+//!
+//! Transport bawaan: Web Push (default), FCM, APNs, dan webhook (`crate::webhook_transport`).
+
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use web_push::Urgency;
+
+use crate::state::StoredSubscription;
+
+/// Klasifikasi error pengiriman.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Gangguan sementara (jaringan, 429, 5xx): boleh dicoba ulang.
+    Retryable,
+    /// Ditolak dan tidak akan berhasil jika diulang (payload / kredensial salah).
+    Permanent,
+    /// Endpoint / token sudah tidak berlaku; subscription dipangkas.
+    Expired,
+}
+
+#[derive(Debug)]
+pub struct TransportError {
+    pub class: ErrorClass,
+    pub message: String,
+}
+
+impl TransportError {
+    pub fn new(class: ErrorClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+        }
+    }
+
+    pub fn retryable(message: impl Into<String>) -> Self {
+        Self::new(ErrorClass::Retryable, message)
+    }
+
+    pub fn permanent(message: impl Into<String>) -> Self {
+        Self::new(ErrorClass::Permanent, message)
+    }
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Kirim payload bertanda tangan (`PushService::seal`) ke satu subscription.
+    async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        urgency: Option<Urgency>,
+    ) -> Result<(), TransportError>;
+
+    /// Klasifikasi respons HTTP gagal. Default: 404/410 = expired, 408/429/5xx = retryable.
+    fn classify(&self, status: u16, _body: &str) -> ErrorClass {
+        match status {
+            404 | 410 => ErrorClass::Expired,
+            408 | 429 | 500..=599 => ErrorClass::Retryable,
+            _ => ErrorClass::Permanent,
+        }
+    }
+}
+
+/// Field notifikasi dari envelope bertanda tangan, untuk transport yang punya format sendiri.
+pub struct Notification {
+    /// Isi envelope `{"payload","signature"}` apa adanya.
+    pub envelope: serde_json::Value,
+    /// Payload JSON di dalam envelope.
+    pub inner: serde_json::Value,
+}
+
+impl Notification {
+    pub fn parse(payload: &[u8]) -> Self {
+        let envelope: serde_json::Value = serde_json::from_slice(payload).unwrap_or_default();
+        let inner = envelope["payload"]
+            .as_str()
+            .and_then(|p| serde_json::from_str(p).ok())
+            .unwrap_or_default();
+        Self { envelope, inner }
+    }
+
+    /// Field dari root payload (`/notify`) atau `data` (trigger).
+    pub fn field(&self, name: &str) -> Option<serde_json::Value> {
+        self.inner
+            .get(name)
+            .filter(|v| !v.is_null())
+            .or_else(|| self.inner["data"].get(name).filter(|v| !v.is_null()))
+            .cloned()
+    }
+
+    /// Teks field; kosong jika tidak ada.
+    pub fn text(&self, name: &str) -> String {
+        match self.field(name) {
+            Some(serde_json::Value::String(s)) => s,
+            Some(other) => other.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Judul: `title`, atau nama event untuk trigger tanpa title.
+    pub fn title(&self) -> String {
+        let title = self.text("title");
+        if title.is_empty() {
+            self.inner["event"].as_str().unwrap_or_default().to_string()
+        } else {
+            title
+        }
+    }
+}
+
+/// Token auth per app (access token FCM, provider token APNs). Setiap app punya lock sendiri,
+/// jadi refresh token satu app (query DB / HTTP) tidak menahan pengiriman app lain.
+pub struct TokenCache<T> {
+    slots: std::sync::Mutex<HashMap<i32, Arc<Mutex<Option<T>>>>>,
+}
+
+impl<T> Default for TokenCache<T> {
+    fn default() -> Self {
+        Self {
+            slots: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> TokenCache<T> {
+    /// Slot token app; kunci slot ini selama memeriksa / memperbarui token.
+    pub fn slot(&self, app_id: i32) -> Arc<Mutex<Option<T>>> {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.entry(app_id).or_default().clone()
+    }
+
+    /// Buang token app (refresh yang sedang berjalan menulis ke slot lama yang sudah dilepas).
+    pub fn forget(&self, app_id: i32) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.remove(&app_id);
+    }
+}
+
+/// Server HTTP lokal untuk pengujian transport (pengganti FCM / APNs / token endpoint).
+#[cfg(test)]
+pub(crate) mod mock {
+    use axum::{
+        body::Bytes,
+        extract::State,
+        http::{HeaderMap, StatusCode, Uri},
+        Router,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Request yang diterima mock.
+    #[derive(Clone, Debug)]
+    pub struct Recorded {
+        pub path: String,
+        pub headers: HeaderMap,
+        pub body: String,
+    }
+
+    impl Recorded {
+        pub fn header(&self, name: &str) -> &str {
+            self.headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+        }
+
+        pub fn json(&self) -> serde_json::Value {
+            serde_json::from_str(&self.body).expect("body JSON")
+        }
+    }
+
+    type Responder = Arc<dyn Fn(&Recorded) -> (u16, String) + Send + Sync>;
+
+    #[derive(Clone)]
+    struct Mock {
+        requests: Arc<Mutex<Vec<Recorded>>>,
+        respond: Responder,
+    }
+
+    /// Jalankan mock di port acak: setiap request dicatat lalu dibalas `respond` (status, body).
+    /// Return (base URL, request yang tercatat).
+    pub async fn serve(
+        respond: impl Fn(&Recorded) -> (u16, String) + Send + Sync + 'static,
+    ) -> (String, Arc<Mutex<Vec<Recorded>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let mock = Mock {
+            requests: requests.clone(),
+            respond: Arc::new(respond),
+        };
+        let app = Router::new().fallback(handle).with_state(mock);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock");
+        let addr = listener.local_addr().expect("mock addr");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}", addr), requests)
+    }
+
+    async fn handle(
+        State(mock): State<Mock>,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> (StatusCode, String) {
+        let recorded = Recorded {
+            path: uri.path().to_string(),
+            headers,
+            body: String::from_utf8_lossy(&body).into_owned(),
+        };
+        let (status, body) = (mock.respond)(&recorded);
+        mock.requests.lock().expect("mock lock").push(recorded);
+        (StatusCode::from_u16(status).expect("status"), body)
+    }
+}
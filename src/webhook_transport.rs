@@ -0,0 +1,156 @@
+//! Transport webhook: subscription `type: "webhook"` dengan `endpoint` = URL tujuan. Payload
+//! di-POST sebagai JSON sesuai `format`:
+//! - `json` (default): envelope bertanda tangan apa adanya (`{"payload","signature"}`),
+//! - `slack` / `discord` / `teams`: pesan incoming webhook dari title/body/url notifikasi.
+
+use async_trait::async_trait;
+use isahc::{
+    config::{Configurable, Dialer},
+    HttpClient, Request,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use web_push::Urgency;
+
+use crate::state::StoredSubscription;
+use crate::transport::{Notification, Transport, TransportError};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Batas Discord embed: title 256, description 4096 karakter.
+const DISCORD_TITLE_MAX: usize = 256;
+const DISCORD_DESCRIPTION_MAX: usize = 4096;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    #[default]
+    Json,
+    Slack,
+    Discord,
+    Teams,
+}
+
+impl WebhookFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookFormat::Json => "json",
+            WebhookFormat::Slack => "slack",
+            WebhookFormat::Discord => "discord",
+            WebhookFormat::Teams => "teams",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Self::Json, Self::Slack, Self::Discord, Self::Teams]
+            .into_iter()
+            .find(|f| f.as_str() == s)
+    }
+}
+
+/// Escape karakter kontrol mrkdwn Slack.
+fn slack_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Body request untuk `format` dari payload bertanda tangan.
+pub fn format_message(format: WebhookFormat, payload: &[u8]) -> serde_json::Value {
+    let n = Notification::parse(payload);
+    let title = n.title();
+    let body = n.text("body");
+    let url = Some(n.text("url")).filter(|u| !u.is_empty());
+    match format {
+        WebhookFormat::Json => n.envelope,
+        WebhookFormat::Slack => {
+            let mut text = format!("*{}*", slack_escape(&title));
+            if !body.is_empty() {
+                text.push('\n');
+                text.push_str(&slack_escape(&body));
+            }
+            if let Some(url) = &url {
+                text.push_str(&format!("\n<{}|Buka>", url));
+            }
+            serde_json::json!({ "text": text })
+        }
+        WebhookFormat::Discord => {
+            let mut embed = serde_json::json!({
+                "title": crate::payload::truncate(&title, DISCORD_TITLE_MAX),
+                "description": crate::payload::truncate(&body, DISCORD_DESCRIPTION_MAX)
+            });
+            if let Some(url) = url {
+                embed["url"] = url.into();
+            }
+            serde_json::json!({ "embeds": [embed] })
+        }
+        WebhookFormat::Teams => {
+            let mut card = serde_json::json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "summary": title,
+                "title": title,
+                "text": body
+            });
+            if let Some(url) = url {
+                card["potentialAction"] = serde_json::json!([{
+                    "@type": "OpenUri",
+                    "name": "Buka",
+                    "targets": [{ "os": "default", "uri": url }]
+                }]);
+            }
+            card
+        }
+    }
+}
+
+pub struct WebhookTransport {
+    client: HttpClient,
+}
+
+impl WebhookTransport {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            client: HttpClient::builder().timeout(REQUEST_TIMEOUT).build()?,
+        })
+    }
+
+    /// POST body JSON ke `url`. Host di-resolve dan dicek dulu (hanya alamat publik), lalu
+    /// koneksi dipin ke alamat tersebut.
+    pub async fn post(&self, url: &str, body: &serde_json::Value) -> Result<(), TransportError> {
+        let addr = crate::webhooks::resolve_public(url)
+            .await
+            .map_err(TransportError::permanent)?;
+        let request = Request::post(url)
+            .dial(Dialer::ip_socket(addr))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .map_err(|e| TransportError::permanent(e.to_string()))?;
+        let response = self
+            .client
+            .send_async(request)
+            .await
+            .map_err(|e| TransportError::retryable(format!("webhook request: {}", e)))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(TransportError::new(
+                self.classify(status.as_u16(), ""),
+                format!("webhook {}", status),
+            ))
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for WebhookTransport {
+    async fn send(
+        &self,
+        subscription: &StoredSubscription,
+        payload: &[u8],
+        _urgency: Option<Urgency>,
+    ) -> Result<(), TransportError> {
+        let body = format_message(subscription.format.unwrap_or_default(), payload);
+        self.post(&subscription.endpoint, &body).await
+    }
+}
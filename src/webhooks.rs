@@ -0,0 +1,299 @@
+//! Webhook keluar: backend pemilik app diberi tahu saat device subscribe/unsubscribe,
+//! subscription dipangkas (endpoint kedaluwarsa), atau notifikasi diklik/ditutup.
+//!
+//! - Dikonfigurasi per app (`keys.id`) lewat `/api/webhooks` dan hanya menerima event
+//!   subscription app tersebut; `events` kosong = semua event.
+//! - Body JSON `{id, event, created_at, data}`, ditandatangani HMAC-SHA256 dengan secret
+//!   webhook: header `X-Webhook-Signature: sha256=<hex>` atas `<timestamp>.<body>`,
+//!   timestamp (detik Unix) di `X-Webhook-Timestamp`.
+//! - Gagal (bukan 2xx / error jaringan) diulang sampai `MAX_ATTEMPTS` kali dengan backoff
+//!   eksponensial; setiap percobaan dicatat di tabel `webhook_deliveries`.
+
+use isahc::{
+    config::{Configurable, Dialer},
+    HttpClient, Request,
+};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::signature;
+
+pub const SUBSCRIPTION_CREATED: &str = "subscription.created";
+pub const SUBSCRIPTION_DELETED: &str = "subscription.deleted";
+pub const SUBSCRIPTION_PRUNED: &str = "subscription.pruned";
+pub const NOTIFICATION_CLICKED: &str = "notification.clicked";
+pub const NOTIFICATION_CLOSED: &str = "notification.closed";
+pub const EVENTS: [&str; 5] = [
+    SUBSCRIPTION_CREATED,
+    SUBSCRIPTION_DELETED,
+    SUBSCRIPTION_PRUNED,
+    NOTIFICATION_CLICKED,
+    NOTIFICATION_CLOSED,
+];
+
+const MAX_ATTEMPTS: u32 = 5;
+/// Jeda sebelum percobaan ke-2; berlipat dua setiap percobaan berikutnya.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookRow {
+    pub id: i32,
+    pub key_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DeliveryRow {
+    pub id: i64,
+    pub delivery_id: String,
+    pub event: String,
+    pub attempt: i32,
+    pub status_code: Option<i32>,
+    pub error: Option<String>,
+    pub success: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookBody {
+    pub key_id: i32,
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookBody {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Secret HMAC acak (hex 32 byte).
+pub fn generate_secret() -> String {
+    random_hex(32)
+}
+
+pub fn validate_url(url: &str) -> Result<(), String> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Ok(())
+    } else {
+        Err("URL webhook harus http:// atau https://".to_string())
+    }
+}
+
+/// Alamat yang boleh dihubungi URL dari luar: bukan loopback, jaringan privat, link-local,
+/// CGNAT, multicast, dsb.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+                || a >= 240)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Resolve host URL lalu pastikan semua alamatnya publik (cegah SSRF ke jaringan internal).
+/// Return alamat yang dipakai untuk koneksi, supaya DNS tidak di-resolve ulang ke alamat lain.
+pub async fn resolve_public(url: &str) -> Result<SocketAddr, String> {
+    validate_url(url)?;
+    let uri: isahc::http::Uri = url.parse().map_err(|_| "URL webhook tidak valid".to_string())?;
+    let host = uri
+        .host()
+        .ok_or_else(|| "URL webhook tanpa host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("host webhook tidak dapat di-resolve: {}", e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err("host webhook tidak dapat di-resolve".to_string());
+    }
+    if addrs.iter().any(|a| !is_public_ip(a.ip())) {
+        return Err("URL webhook mengarah ke alamat lokal / jaringan privat".to_string());
+    }
+    Ok(addrs[0])
+}
+
+pub fn validate_events(events: &[String]) -> Result<(), String> {
+    match events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+        Some(e) => Err(format!(
+            "event tidak dikenal: {} (pilihan: {})",
+            e,
+            EVENTS.join(", ")
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Pengirim webhook; `dispatch` tidak memblokir (dikirim di task terpisah).
+#[derive(Clone)]
+pub struct Webhooks {
+    db: PgPool,
+    client: Arc<HttpClient>,
+}
+
+impl Webhooks {
+    pub fn new(db: PgPool) -> anyhow::Result<Self> {
+        let client = HttpClient::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self {
+            db,
+            client: Arc::new(client),
+        })
+    }
+
+    /// Kirim `event` milik app `app_id` ke webhook aktif app tersebut yang berlangganan event
+    /// ini. Event tanpa app (subscription tanpa `app_id`) tidak dikirim ke mana pun.
+    pub fn dispatch(&self, event: &'static str, app_id: Option<i32>, data: serde_json::Value) {
+        let Some(app_id) = app_id else {
+            return;
+        };
+        let this = self.clone();
+        tokio::spawn(async move {
+            let targets: Vec<(i32, String, String)> = match sqlx::query_as(
+                "SELECT id, url, secret FROM webhooks WHERE active AND key_id = $2 AND (cardinality(events) = 0 OR $1 = ANY(events))",
+            )
+            .bind(event)
+            .bind(app_id)
+            .fetch_all(&this.db)
+            .await
+            {
+                Ok(rows) => rows,
+                Err(e) => {
+                    warn!(error = %e, event, "load webhooks failed");
+                    return;
+                }
+            };
+            for (webhook_id, url, secret) in targets {
+                let this = this.clone();
+                let data = data.clone();
+                tokio::spawn(async move {
+                    this.deliver(webhook_id, &url, &secret, event, data).await;
+                });
+            }
+        });
+    }
+
+    async fn deliver(
+        &self,
+        webhook_id: i32,
+        url: &str,
+        secret: &str,
+        event: &str,
+        data: serde_json::Value,
+    ) {
+        let delivery_id = random_hex(16);
+        let body = serde_json::json!({
+            "id": delivery_id,
+            "event": event,
+            "created_at": chrono::Utc::now(),
+            "data": data
+        })
+        .to_string();
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let (status_code, error) =
+                match self.post(url, secret, event, &delivery_id, &body).await {
+                    Ok(status) => (Some(status), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+            let success = status_code.is_some_and(|s| (200..300).contains(&s));
+            if let Err(e) = sqlx::query(
+                "INSERT INTO webhook_deliveries (webhook_id, delivery_id, event, payload, attempt, status_code, error, success) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )
+            .bind(webhook_id)
+            .bind(&delivery_id)
+            .bind(event)
+            .bind(&body)
+            .bind(attempt as i32)
+            .bind(status_code.map(i32::from))
+            .bind(&error)
+            .bind(success)
+            .execute(&self.db)
+            .await
+            {
+                warn!(error = %e, "log webhook delivery failed");
+            }
+            if success {
+                info!(webhook_id, event, attempt, "webhook delivered");
+                return;
+            }
+            warn!(
+                webhook_id,
+                event,
+                attempt,
+                ?status_code,
+                ?error,
+                "webhook delivery failed"
+            );
+            if attempt < MAX_ATTEMPTS {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+
+    async fn post(
+        &self,
+        url: &str,
+        secret: &str,
+        event: &str,
+        delivery_id: &str,
+        body: &str,
+    ) -> anyhow::Result<u16> {
+        // Dicek ulang saat kirim (DNS bisa berubah) dan koneksi dipin ke alamat yang dicek.
+        let addr = resolve_public(url).await.map_err(anyhow::Error::msg)?;
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature = signature::sign(secret, &format!("{}.{}", timestamp, body));
+        let request = Request::post(url)
+            .dial(Dialer::ip_socket(addr))
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", event)
+            .header("X-Webhook-Id", delivery_id)
+            .header("X-Webhook-Timestamp", &timestamp)
+            .header("X-Webhook-Signature", format!("sha256={}", signature))
+            .body(body.to_string())?;
+        let response = self.client.send_async(request).await?;
+        Ok(response.status().as_u16())
+    }
+}
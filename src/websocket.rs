@@ -0,0 +1,272 @@
+//! Endpoint WebSocket yang berbicara protokol client Pusher Channels (protocol 7),
+//! sehingga pusher-js bisa langsung konek ke server ini.
+//!
+//! Pesan yang didukung dari client: `pusher:subscribe`, `pusher:unsubscribe`, `pusher:ping`.
+//! Channel `private-*` / `presence-*` butuh `auth` (lihat `crate::signature`); member presence
+//! dicatat di `crate::presence`.
+//! Event dari trigger dikirim sebagai `{ "event", "channel", "data" }` dengan `data` berupa
+//! string JSON, sama seperti Pusher.
+
+use axum::extract::ws::{Message, WebSocket};
+use rand_core::{OsRng, RngCore};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::channel::{self, ChannelFilter};
+use crate::presence::{self, PresenceGuard, PRESENCE_PREFIX};
+use crate::realtime::RealtimeEvent;
+use crate::signature;
+use crate::state::AppState;
+
+/// Detik tanpa aktivitas sebelum client mengirim `pusher:ping`.
+const ACTIVITY_TIMEOUT: u64 = 120;
+
+/// Kode error Pusher (lihat dokumentasi protokol Pusher).
+pub const ERROR_APP_NOT_FOUND: u16 = 4001;
+const ERROR_GENERIC: u16 = 4200;
+
+#[derive(Deserialize)]
+struct ClientMessage {
+    event: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+/// Socket ID format Pusher: `<angka>.<angka>`.
+pub fn generate_socket_id() -> String {
+    format!("{}.{}", OsRng.next_u32(), OsRng.next_u32())
+}
+
+fn frame(event: &str, channel: Option<&str>, data: &serde_json::Value) -> Message {
+    // Pusher mengirim `data` sebagai string JSON, bukan object.
+    let data = match data {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let mut msg = serde_json::json!({ "event": event, "data": data });
+    if let Some(ch) = channel {
+        msg["channel"] = serde_json::Value::String(ch.to_string());
+    }
+    Message::Text(msg.to_string())
+}
+
+pub fn error_frame(code: u16, message: &str) -> Message {
+    frame(
+        "pusher:error",
+        None,
+        &serde_json::json!({ "code": code, "message": message }),
+    )
+}
+
+/// Channel yang di-subscribe socket ini dan menjadi tujuan event (yang sudah lolos filter).
+/// Broadcast (tanpa channel) dikirim ke semua channel socket.
+fn target_channels<'a>(filter: &ChannelFilter, subscribed: &'a [String]) -> Vec<&'a String> {
+    if filter.any.is_empty() && filter.all.is_empty() {
+        return subscribed.iter().collect();
+    }
+    subscribed
+        .iter()
+        .filter(|p| {
+            filter
+                .any
+                .iter()
+                .chain(&filter.all)
+                .any(|ch| channel::matches(p, ch))
+        })
+        .collect()
+}
+
+/// Identitas app (baris tabel keys) yang dipakai koneksi, untuk verifikasi auth channel.
+pub struct App {
+    pub id: i32,
+    pub public_key: String,
+    pub secret: String,
+}
+
+struct Connection {
+    state: AppState,
+    app: App,
+    socket_id: String,
+    subscribed: Vec<String>,
+    presence: PresenceGuard,
+}
+
+pub async fn serve(mut socket: WebSocket, state: AppState, app: App) {
+    let socket_id = generate_socket_id();
+    let established = serde_json::json!({
+        "socket_id": socket_id,
+        "activity_timeout": ACTIVITY_TIMEOUT
+    });
+    if socket
+        .send(frame("pusher:connection_established", None, &established))
+        .await
+        .is_err()
+    {
+        return;
+    }
+    info!(socket_id = %socket_id, "websocket connected");
+
+    let mut rx = state.realtime.subscribe();
+    let mut conn = Connection {
+        presence: PresenceGuard {
+            registry: state.presence.clone(),
+            hub: state.realtime.clone(),
+            app_id: app.id,
+            socket_id: socket_id.clone(),
+            channels: Vec::new(),
+        },
+        state,
+        app,
+        socket_id,
+        subscribed: Vec::new(),
+    };
+    loop {
+        tokio::select! {
+            incoming = socket.recv() => {
+                let text = match incoming {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
+                    Some(Ok(_)) => continue,
+                };
+                if let Some(reply) = conn.handle_client_message(&text) {
+                    if socket.send(reply).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            ev = rx.recv() => {
+                let ev: Arc<RealtimeEvent> = match ev {
+                    Ok(ev) => ev,
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!(socket_id = %conn.socket_id, skipped, "websocket lagged, events dropped");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !ev.is_for(&conn.socket_id, Some(conn.app.id), &conn.subscribed) {
+                    continue;
+                }
+                for ch in target_channels(&ev.filter, &conn.subscribed) {
+                    if socket.send(frame(&ev.event, Some(ch), &ev.data)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+    info!(socket_id = %conn.socket_id, "websocket disconnected");
+}
+
+fn subscription_error(channel: &str, kind: &str, error: &str, status: u16) -> Message {
+    frame(
+        "pusher:subscription_error",
+        Some(channel),
+        &serde_json::json!({ "type": kind, "error": error, "status": status }),
+    )
+}
+
+impl Connection {
+    fn handle_client_message(&mut self, text: &str) -> Option<Message> {
+        let msg: ClientMessage = match serde_json::from_str(text) {
+            Ok(m) => m,
+            Err(_) => return Some(error_frame(ERROR_GENERIC, "invalid message")),
+        };
+        let field = |name: &str| msg.data.get(name).and_then(|v| v.as_str());
+        let channel_name = field("channel").unwrap_or("").to_string();
+        match msg.event.as_str() {
+            "pusher:ping" => Some(frame("pusher:pong", None, &serde_json::json!({}))),
+            "pusher:subscribe" => {
+                let channel_data = field("channel_data");
+                Some(self.subscribe(&channel_name, field("auth"), channel_data))
+            }
+            "pusher:unsubscribe" => {
+                self.subscribed.retain(|c| *c != channel_name);
+                if channel_name.starts_with(PRESENCE_PREFIX) {
+                    self.presence.channels.retain(|c| *c != channel_name);
+                    self.state.presence.leave_and_announce(
+                        &self.state.realtime,
+                        self.app.id,
+                        &channel_name,
+                        &self.socket_id,
+                    );
+                }
+                None
+            }
+            // Client events (client-*) tidak didukung; event lain diabaikan.
+            _ => None,
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        channel_name: &str,
+        auth: Option<&str>,
+        channel_data: Option<&str>,
+    ) -> Message {
+        let mut succeeded = serde_json::json!({});
+        if presence::requires_auth(channel_name) {
+            if let Err(message) = channel::validate_name(channel_name) {
+                return subscription_error(channel_name, "InvalidChannel", &message, 400);
+            }
+            let is_presence = channel_name.starts_with(PRESENCE_PREFIX);
+            let channel_data = if is_presence { channel_data } else { None };
+            let authorized = auth.is_some_and(|auth| {
+                signature::verify_channel_auth(
+                    &self.app.public_key,
+                    &self.app.secret,
+                    auth,
+                    &self.socket_id,
+                    channel_name,
+                    channel_data,
+                )
+            });
+            if !authorized {
+                return subscription_error(
+                    channel_name,
+                    "AuthError",
+                    "auth signature tidak valid",
+                    403,
+                );
+            }
+            if is_presence {
+                let member = match channel_data.map(presence::parse_channel_data) {
+                    Some(Ok(member)) => member,
+                    Some(Err(message)) => {
+                        return subscription_error(channel_name, "AuthError", &message, 400)
+                    }
+                    None => {
+                        return subscription_error(
+                            channel_name,
+                            "AuthError",
+                            "channel_data wajib diisi",
+                            400,
+                        )
+                    }
+                };
+                if !self.presence.channels.iter().any(|c| c == channel_name) {
+                    self.presence.channels.push(channel_name.to_string());
+                }
+                self.state.presence.join_and_announce(
+                    &self.state.realtime,
+                    self.app.id,
+                    channel_name,
+                    &self.socket_id,
+                    &member,
+                );
+                succeeded = self.state.presence.subscription_data(self.app.id, channel_name);
+            }
+        } else if let Err(message) = channel::validate_pattern(channel_name) {
+            return subscription_error(channel_name, "InvalidChannel", &message, 400);
+        }
+        if !self.subscribed.iter().any(|c| c == channel_name) {
+            self.subscribed.push(channel_name.to_string());
+        }
+        frame(
+            "pusher_internal:subscription_succeeded",
+            Some(channel_name),
+            &succeeded,
+        )
+    }
+}
@@ -215,7 +215,14 @@ $(function () {
   }
 
   if ('serviceWorker' in navigator) {
-    navigator.serviceWorker.register('/sw.js?v=4', { scope: '/' })
+    // Pin key verifikasi payload: public key app jika halaman milik app, selain itu key server.
+    var signingKey = window.PUSH_NOTIF_APP_KEY
+      ? Promise.resolve(window.PUSH_NOTIF_APP_KEY)
+      : fetch('/signing-public-key').then(function (res) { return res.json(); }).then(function (j) { return j.publicKey; });
+    signingKey
+      .then(function (key) {
+        return navigator.serviceWorker.register('/sw.js?v=8&signingKey=' + encodeURIComponent(key), { scope: '/' });
+      })
       .then(function (reg) {
         reg.update();
         if (reg.waiting) reg.waiting.postMessage({ type: 'SKIP_WAITING' });
@@ -2,15 +2,22 @@
  * SDK Push Notif gaya Pusher: channel + event + bind.
  * Pakai: PushNotif.subscribe('channel-name').bind('event-name', function(data) { ... })
  * Sebelum terima event, panggil PushNotif.requestSubscription() (atau klik Subscribe di halaman).
+ * Jika Web Push tidak tersedia (browser tidak mendukung / izin notifikasi ditolak),
+ * SDK otomatis memakai stream SSE (/stream) selama halaman terbuka.
  */
 (function (global) {
   'use strict';
 
   var API_BASE = (typeof global.PUSH_NOTIF_API_BASE !== 'undefined' ? global.PUSH_NOTIF_API_BASE : '');
+  // Public key app (dashboard); server me-resolve app dari key ini.
+  var APP_KEY = global.PUSH_NOTIF_APP_KEY || null;
   var channels = {};
   var channelList = [];
   var bindings = {};
   var vapidPublicKey = null;
+  var eventSource = null;
+  var subscriptionId = null;
+  var socketId = null;
 
   function getVapidPublicKey() {
     if (vapidPublicKey) return Promise.resolve(vapidPublicKey);
@@ -32,6 +39,7 @@
     if (!channels[name]) {
       if (channelList.indexOf(name) === -1) channelList.push(name);
       channels[name] = { name: name, bindings: {} };
+      if (eventSource) connectStream();
     }
     return channels[name];
   }
@@ -73,8 +81,41 @@
     }
   }
 
+  function webPushSupported() {
+    return typeof global.Notification !== 'undefined' &&
+      typeof navigator !== 'undefined' && 'serviceWorker' in navigator &&
+      typeof global.PushManager !== 'undefined';
+  }
+
+  /** Fallback realtime: terima event lewat SSE selama halaman terbuka. */
+  function connectStream() {
+    if (typeof global.EventSource === 'undefined') return false;
+    if (eventSource) eventSource.close();
+    // Channel private/presence butuh auth lewat POST /stream/subscribe, tidak ikut di sini.
+    var chanList = channelList.filter(function (c) { return !/^(private|presence)-/.test(c); });
+    if (!chanList.length) chanList = ['default'];
+    eventSource = new EventSource(API_BASE + '/stream?channels=' + encodeURIComponent(chanList.join(',')) +
+      (APP_KEY ? '&app=' + encodeURIComponent(APP_KEY) : ''));
+    eventSource.addEventListener('connection_established', function (e) {
+      try { socketId = JSON.parse(e.data).socket_id; } catch (err) {}
+    });
+    eventSource.onmessage = function (e) {
+      var payload;
+      try { payload = JSON.parse(e.data); } catch (err) { return; }
+      var data = payload.data || {};
+      onPushReceived({ event: payload.event, channel: payload.channel, data: data, title: data.title, body: data.body });
+    };
+    return true;
+  }
+
+  function streamFallback(reason) {
+    if (!connectStream()) return Promise.reject(reason);
+    return { ok: true, transport: 'sse' };
+  }
+
   function requestSubscription() {
     var chanList = channelList.length ? channelList : ['default'];
+    if (!webPushSupported()) return Promise.resolve(streamFallback(new Error('Web Push tidak didukung')));
     return (Notification.requestPermission ? Notification.requestPermission() : Promise.resolve('denied'))
       .then(function (permission) {
         if (permission !== 'granted') return Promise.reject(new Error('Permission ' + permission));
@@ -101,23 +142,68 @@
         return fetch(API_BASE + '/subscribe', {
           method: 'POST',
           headers: { 'Content-Type': 'application/json' },
-          body: JSON.stringify({ endpoint: raw.endpoint, keys: raw.keys, channels: chanList })
+          body: JSON.stringify({
+            endpoint: raw.endpoint,
+            keys: raw.keys,
+            channels: chanList,
+            app: APP_KEY || undefined,
+            // ID user aplikasi (opsional) untuk notifikasi per user + fallback email, beserta
+            // HMAC-SHA256 hex atas user_id dengan secret app (dibuat di backend aplikasi).
+            user_id: global.PUSH_NOTIF_USER_ID || undefined,
+            user_auth: global.PUSH_NOTIF_USER_AUTH || undefined,
+            // Quiet hours subscriber (opsional), mis. { start: '22:00', end: '07:00' }.
+            quiet_hours: global.PUSH_NOTIF_QUIET_HOURS ? {
+              start: global.PUSH_NOTIF_QUIET_HOURS.start,
+              end: global.PUSH_NOTIF_QUIET_HOURS.end,
+              timezone: global.PUSH_NOTIF_QUIET_HOURS.timezone ||
+                Intl.DateTimeFormat().resolvedOptions().timeZone
+            } : undefined
+          })
         });
       })
       .then(function (r) {
         if (!r.ok) return Promise.reject(new Error(r.statusText));
         return r.json();
+      }, function (err) {
+        return streamFallback(err);
+      })
+      .then(function (j) {
+        if (j && j.id) subscriptionId = j.id;
+        return j;
+      });
+  }
+
+  /** Berhenti menerima Web Push di browser ini (server menghapus subscription-nya). */
+  function unsubscribe() {
+    if (!webPushSupported()) return Promise.resolve({ ok: true });
+    return navigator.serviceWorker.ready
+      .then(function (reg) { return reg.pushManager.getSubscription(); })
+      .then(function (subscription) {
+        if (!subscription) return { ok: true };
+        var endpoint = subscription.endpoint;
+        // keys.auth hanya diketahui browser ini, jadi server bisa memastikan pemiliknya.
+        var auth = subscription.toJSON ? subscription.toJSON().keys.auth : undefined;
+        return subscription.unsubscribe().then(function () {
+          subscriptionId = null;
+          return fetch(API_BASE + '/unsubscribe', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ endpoint: endpoint, auth: auth })
+          }).then(function (r) { return r.json(); });
+        });
       });
   }
 
-  function trigger(channelsToSend, eventName, data) {
+  /** exclude (opsional): ID subscription / socket ID yang tidak ikut menerima, mis. PushNotif.selfIds. */
+  function trigger(channelsToSend, eventName, data, exclude) {
     return fetch(API_BASE + '/trigger', {
       method: 'POST',
       headers: { 'Content-Type': 'application/json' },
       body: JSON.stringify({
         channels: Array.isArray(channelsToSend) ? channelsToSend : [channelsToSend],
         event: eventName,
-        data: data || {}
+        data: data || {},
+        exclude: exclude || []
       })
     }).then(function (r) { return r.json(); });
   }
@@ -142,7 +228,12 @@
   global.PushNotif = {
     subscribe: subscribe,
     requestSubscription: requestSubscription,
+    unsubscribe: unsubscribe,
     trigger: trigger,
-    get channels() { return channelList.slice(); }
+    get channels() { return channelList.slice(); },
+    get subscriptionId() { return subscriptionId; },
+    get socketId() { return socketId; },
+    /** ID milik halaman ini, untuk trigger(..., PushNotif.selfIds) agar tidak menerima event sendiri. */
+    get selfIds() { return [subscriptionId, socketId].filter(Boolean); }
   };
 })(typeof window !== 'undefined' ? window : this);
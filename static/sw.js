@@ -1,38 +1,113 @@
-self.addEventListener('push', function (event) {
+function parsePushData(eventData) {
+  if (!eventData) return null;
+  try {
+    return eventData.json();
+  } catch (e) {
+    return null;
+  }
+}
+
+// Verifikasi signature payload (ECDSA P-256 / SHA-256). Push milik app ditandatangani dengan
+// key app, jadi halaman wajib mempin public key app lewat query ?signingKey=<base64url> di URL
+// script service worker. Tanpa pin, dipakai key server dari /signing-public-key yang di-cache
+// saat install (trust on first use).
+var SIGNING_KEY_CACHE = 'push-notif-signing-key';
+var pinnedSigningKey = new URL(self.location.href).searchParams.get('signingKey');
+
+function base64UrlToBytes(s) {
+  var padding = '='.repeat((4 - (s.length % 4)) % 4);
+  var raw = atob((s + padding).replace(/-/g, '+').replace(/_/g, '/'));
+  var out = new Uint8Array(raw.length);
+  for (var i = 0; i < raw.length; ++i) out[i] = raw.charCodeAt(i);
+  return out;
+}
+
+function signingKeyUrl() {
+  return new URL('signing-public-key', self.registration.scope).href;
+}
+
+function fetchSigningKey() {
+  return caches.open(SIGNING_KEY_CACHE).then(function (cache) {
+    return fetch(signingKeyUrl(), { credentials: 'omit' }).then(function (res) {
+      if (!res.ok) throw new Error('HTTP ' + res.status);
+      return cache.put(signingKeyUrl(), res.clone()).then(function () { return res.json(); });
+    });
+  }).then(function (j) { return j.publicKey; });
+}
+
+function getSigningKey() {
+  if (pinnedSigningKey) return Promise.resolve(pinnedSigningKey);
+  return caches.open(SIGNING_KEY_CACHE)
+    .then(function (cache) { return cache.match(signingKeyUrl()); })
+    .then(function (res) { return res ? res.json().then(function (j) { return j.publicKey; }) : fetchSigningKey(); });
+}
+
+// Return payload JSON di dalam envelope jika signature valid; reject jika tidak.
+function verifyEnvelope(envelope) {
+  if (!envelope || typeof envelope.payload !== 'string' || typeof envelope.signature !== 'string') {
+    return Promise.reject(new Error('payload tidak bertanda tangan'));
+  }
+  return getSigningKey()
+    .then(function (publicKey) {
+      return crypto.subtle.importKey('raw', base64UrlToBytes(publicKey), { name: 'ECDSA', namedCurve: 'P-256' }, false, ['verify']);
+    })
+    .then(function (key) {
+      return crypto.subtle.verify(
+        { name: 'ECDSA', hash: 'SHA-256' },
+        key,
+        base64UrlToBytes(envelope.signature),
+        new TextEncoder().encode(envelope.payload)
+      );
+    })
+    .then(function (valid) {
+      if (!valid) throw new Error('signature payload tidak valid');
+      return JSON.parse(envelope.payload);
+    });
+}
+
+self.addEventListener('install', function (event) {
+  if (!pinnedSigningKey) event.waitUntil(fetchSigningKey().catch(function () {}));
+});
+
+// Mode fetch-on-push: push hanya membawa payload_id, payload lengkap diambil dari server.
+// Jika gagal, tampilkan ringkasan (title/body terpotong) yang ikut di push.
+function resolvePayload(data) {
+  if (!data || !data.payload_id) return Promise.resolve(data);
+  return getSigningKey()
+    .then(function (publicKey) {
+      var url = new URL('payloads/' + encodeURIComponent(data.payload_id), self.registration.scope);
+      url.searchParams.set('key', publicKey);
+      return fetch(url.href, { credentials: 'omit' });
+    })
+    .then(function (res) {
+      if (!res.ok) throw new Error('HTTP ' + res.status);
+      return res.json();
+    })
+    .then(verifyEnvelope)
+    .catch(function () { return data; });
+}
+
+function showPush(data) {
   var title = 'Notifikasi';
   var body = 'Pesan baru dari Web Push.';
   var icon = null;
-  var payloadData = null;
-  if (event.data) {
-    try {
-      var raw = typeof event.data.text === 'function' ? event.data.text() : (event.data.text || '');
-      if (raw && raw.length > 0) {
-        var data = JSON.parse(raw);
-        payloadData = data;
-        if (data.event && data.data) {
-          if (data.data.title) title = String(data.data.title);
-          if (data.data.body) body = String(data.data.body);
-          if (data.data.icon) icon = String(data.data.icon);
-          if (!title || title.length === 0) title = String(data.event);
-          if (!body || body.length === 0) body = JSON.stringify(data.data);
-        } else {
-          if (data.title) title = String(data.title);
-          if (data.body) body = String(data.body);
-          if (data.icon) icon = String(data.icon);
-        }
-      }
-    } catch (e) {
-      try {
-        if (event.data.json) {
-          var data = event.data.json();
-          payloadData = data;
-          if (data && data.title) title = String(data.title);
-          if (data && data.body) body = String(data.body);
-          if (data && data.icon) icon = String(data.icon);
-        }
-      } catch (e2) {
-        if (event.data.text) body = event.data.text() || body;
+  var payloadData = data;
+  if (data) {
+    if (data.event && data.data) {
+      // Event dari API Pusher membawa data sebagai string (biasanya JSON).
+      var eventData = data.data;
+      if (typeof eventData === 'string') {
+        try { eventData = JSON.parse(eventData); } catch (e) { eventData = { body: eventData }; }
       }
+      if (eventData.title) title = String(eventData.title);
+      if (eventData.body) body = String(eventData.body);
+      if (eventData.icon) icon = String(eventData.icon);
+      if (!title || title.length === 0) title = String(data.event);
+      if (!body || body.length === 0) body = JSON.stringify(data.data);
+    } else {
+      if (data.title) title = String(data.title);
+      if (data.body) body = String(data.body);
+      if (data.icon) icon = String(data.icon);
     }
   }
   if (!title || title.length === 0) title = 'Notifikasi';
@@ -57,7 +132,10 @@ self.addEventListener('push', function (event) {
     channel.postMessage(payload);
   } catch (e) {}
 
-  var notifOpts = { body: body, tag: tag, requireInteraction: false, icon: icon };
+  // Disimpan di notifikasi untuk laporan klik/tutup (reportInteraction).
+  var notifData = { event: payload.event, channel: payload.channel, title: title };
+  if (payloadData && payloadData.data && payloadData.data.url) notifData.url = String(payloadData.data.url);
+  var notifOpts = { body: body, tag: tag, requireInteraction: false, icon: icon, data: notifData };
 
   function show(opts) {
     return self.registration.showNotification(title, opts || notifOpts);
@@ -66,7 +144,7 @@ self.addEventListener('push', function (event) {
   // Coba dengan icon; jika gagal (icon load error dll), coba tanpa icon agar notifikasi tetap muncul
   var showPromise = show()
     .catch(function () {
-      return show({ body: body, tag: tag, requireInteraction: false });
+      return show({ body: body, tag: tag, requireInteraction: false, data: notifData });
     });
 
   var notifyPromise = showPromise
@@ -80,13 +158,57 @@ self.addEventListener('push', function (event) {
     })
     .catch(function () {});
 
-  event.waitUntil(showPromise.then(function () { return notifyPromise; }).catch(function () {}));
+  return showPromise.then(function () { return notifyPromise; }).catch(function () {});
+}
+
+self.addEventListener('push', function (event) {
+  event.waitUntil(
+    verifyEnvelope(parsePushData(event.data))
+      .then(resolvePayload)
+      .then(function (data) {
+        return showPush(data);
+      })
+      .catch(function (e) {
+        // Push tidak terverifikasi tidak ditampilkan isinya.
+        console.warn('Push ditolak:', e && e.message);
+      })
+  );
 });
 
+// Laporkan klik/tutup notifikasi ke server (diteruskan ke webhook notification.*).
+function reportInteraction(action, notification) {
+  var info = notification.data || {};
+  return self.registration.pushManager.getSubscription()
+    .then(function (sub) {
+      if (!sub) return;
+      return fetch(new URL('interactions', self.registration.scope).href, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        credentials: 'omit',
+        body: JSON.stringify({
+          action: action,
+          event: info.event || null,
+          channel: info.channel || null,
+          title: info.title || null,
+          endpoint: sub.endpoint,
+          // Bukti kepemilikan subscription (lihat /unsubscribe).
+          auth: sub.toJSON().keys.auth
+        })
+      });
+    })
+    .catch(function () {});
+}
+
 self.addEventListener('notificationclick', function (event) {
   event.notification.close();
-  event.waitUntil(
+  event.waitUntil(Promise.all([
+    reportInteraction('clicked', event.notification),
     clients.matchAll({ type: 'window', includeUncontrolled: true }).then(function (clientList) {
+      // Notifikasi dengan url (mis. dari /hooks) membuka tautan tersebut.
+      var url = (event.notification.data && event.notification.data.url) || null;
+      if (url && clients.openWindow) {
+        return clients.openWindow(url);
+      }
       if (clientList.length > 0 && clientList[0].focus) {
         return clientList[0].focus();
       }
@@ -94,5 +216,9 @@ self.addEventListener('notificationclick', function (event) {
         return clients.openWindow('/');
       }
     })
-  );
+  ]));
+});
+
+self.addEventListener('notificationclose', function (event) {
+  event.waitUntil(reportInteraction('closed', event.notification));
 });